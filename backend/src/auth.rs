@@ -0,0 +1,340 @@
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{ContentType, HeaderMap, AUTHORIZATION};
+use actix_web::{get, post, web, Error, HttpMessage, HttpRequest, HttpResponse};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, SaltString};
+use argon2::{Argon2, PasswordHasher, PasswordVerifier};
+use chrono::{DateTime, Duration, Utc};
+use domain::users::{User, UserStore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::OnceLock;
+
+/// How long a login lasts.
+const SESSION_DAYS: i64 = 30;
+
+#[derive(Deserialize)]
+pub struct Credentials {
+    username: String,
+    password: String,
+}
+
+impl Credentials {
+    /// Rejects usernames that would not read well in a URL or a leaderboard,
+    /// and passwords too short to be worth hashing.
+    fn check(&self) -> Result<(), &'static str> {
+        let name_chars = self
+            .username
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+        if !(3..=32).contains(&self.username.len()) || !name_chars {
+            return Err("usernames are 3 to 32 lowercase letters, digits, - or _");
+        }
+        if self.password.chars().count() < 8 {
+            return Err("passwords have at least 8 characters");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct Session {
+    /// Sent back as `Authorization: Bearer <token>`
+    token: String,
+    expires_at: DateTime<Utc>,
+    user: User,
+}
+
+/// A hash of no one's password, checked when a login names an unknown user
+/// so that it takes as long as one with a wrong password.
+static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+
+/// The hash checked for unknown users. Worth calling before serving, so the
+/// first such login is not the one that pays for making it.
+pub fn dummy_hash() -> &'static str {
+    DUMMY_HASH
+        .get_or_init(|| hash_password("not anyone's password").expect("Argon2 hashes any password"))
+}
+
+fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+}
+
+/// The user a request was made by, put in its extensions by [`Authenticate`].
+#[derive(Clone)]
+pub struct CurrentUser(pub User);
+
+/// Sessions are stored under the hash of their token, so a leaked sessions
+/// table does not log anyone in.
+fn token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    value.strip_prefix("Bearer ").map(|t| t.trim().to_string())
+}
+
+fn json<T: Serialize>(mut response: actix_web::HttpResponseBuilder, body: &T) -> HttpResponse {
+    response
+        .content_type(ContentType::json())
+        .body(serde_json::to_string(body).expect(""))
+}
+
+#[post("/register")]
+async fn register(
+    store: web::Data<dyn UserStore>,
+    credentials: web::Json<Credentials>,
+) -> HttpResponse {
+    if let Err(reason) = credentials.check() {
+        return HttpResponse::BadRequest().body(reason);
+    }
+    let credentials = credentials.into_inner();
+    let user = User::new(&credentials.username);
+    // Hashing takes a good part of a second on purpose, off the workers
+    let hashed = web::block(move || hash_password(&credentials.password)).await;
+    let password_hash = match hashed {
+        Ok(Ok(hash)) => hash,
+        _ => return HttpResponse::InternalServerError().finish(),
+    };
+    match store.create_user(&user, &password_hash).await {
+        Ok(true) => json(HttpResponse::Created(), &user),
+        Ok(false) => HttpResponse::Conflict().body("This username is taken"),
+        Err(e) => {
+            eprintln!("Could not create user {}: {}", user.username, e);
+            HttpResponse::ServiceUnavailable().finish()
+        }
+    }
+}
+
+#[post("/login")]
+async fn login(
+    store: web::Data<dyn UserStore>,
+    credentials: web::Json<Credentials>,
+) -> HttpResponse {
+    let (user, password_hash) = match store.user_by_name(&credentials.username).await {
+        Ok(Some((user, hash))) => (Some(user), Some(hash)),
+        Ok(None) => (None, None),
+        Err(_) => return HttpResponse::ServiceUnavailable().finish(),
+    };
+    let password = credentials.into_inner().password;
+    let verified = web::block(move || {
+        let password_hash = password_hash.as_deref().unwrap_or_else(|| dummy_hash());
+        PasswordHash::new(password_hash)
+            .map(|hash| {
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &hash)
+                    .is_ok()
+            })
+            .unwrap_or(false)
+    })
+    .await;
+    let user = match (user, verified) {
+        (Some(user), Ok(true)) => user,
+        _ => return HttpResponse::Unauthorized().finish(),
+    };
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token = hex::encode(bytes);
+    let expires_at = Utc::now() + Duration::days(SESSION_DAYS);
+    if let Err(e) = store
+        .create_session(&token_hash(&token), &user.id, expires_at)
+        .await
+    {
+        eprintln!("Could not start a session for {}: {}", user.username, e);
+        return HttpResponse::ServiceUnavailable().finish();
+    }
+    json(
+        HttpResponse::Ok(),
+        &Session {
+            token,
+            expires_at,
+            user,
+        },
+    )
+}
+
+/// Ends the session the request was made with.
+#[post("/logout")]
+async fn logout(store: web::Data<dyn UserStore>, req: HttpRequest) -> HttpResponse {
+    let token = match bearer_token(req.headers()) {
+        Some(token) => token,
+        None => return HttpResponse::Unauthorized().finish(),
+    };
+    match store.delete_session(&token_hash(&token)).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(_) => HttpResponse::ServiceUnavailable().finish(),
+    }
+}
+
+#[get("/me")]
+async fn me(user: Option<web::ReqData<CurrentUser>>) -> HttpResponse {
+    match user {
+        Some(user) => json(HttpResponse::Ok(), &user.into_inner().0),
+        None => HttpResponse::Unauthorized().finish(),
+    }
+}
+
+/// Looks up the session of requests with a bearer token and attaches its
+/// [`CurrentUser`]. Requests without a valid session go through anonymously,
+/// so each route decides whether it needs one.
+pub struct Authenticate;
+
+impl<S, B> Transform<S, ServiceRequest> for Authenticate
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = AuthenticateMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthenticateMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct AuthenticateMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuthenticateMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        Box::pin(async move {
+            let token = bearer_token(req.headers());
+            let store = req.app_data::<web::Data<dyn UserStore>>().cloned();
+            if let (Some(token), Some(store)) = (token, store) {
+                match store.session_user(&token_hash(&token)).await {
+                    Ok(Some(user)) => {
+                        req.extensions_mut().insert(CurrentUser(user));
+                    }
+                    Ok(None) => {}
+                    Err(e) => eprintln!("Could not look up a session: {}", e),
+                }
+            }
+            service.call(req).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{login, logout, me, register, Authenticate, Credentials};
+    use actix_web::http::header::AUTHORIZATION;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_and_read_body_json, call_service, init_service, TestRequest};
+    use actix_web::{web, App};
+    use domain::memory::MemoryStore;
+    use domain::users::UserStore;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    /// The auth routes over an empty memory store. A macro, as the type of
+    /// the service cannot be written out.
+    macro_rules! app {
+        () => {{
+            let store: Arc<dyn UserStore> = Arc::new(MemoryStore::default());
+            init_service(
+                App::new()
+                    .app_data(web::Data::from(store))
+                    .wrap(Authenticate)
+                    .service(register)
+                    .service(login)
+                    .service(logout)
+                    .service(me),
+            )
+            .await
+        }};
+    }
+
+    fn post(uri: &str, username: &str, password: &str) -> TestRequest {
+        TestRequest::post()
+            .uri(uri)
+            .set_json(json!({ "username": username, "password": password }))
+    }
+
+    #[test]
+    fn checks_credentials() {
+        let credentials = |username: &str, password: &str| Credentials {
+            username: username.to_string(),
+            password: password.to_string(),
+        };
+        assert!(credentials("ann_1", "long enough").check().is_ok());
+        assert!(credentials("an", "long enough").check().is_err());
+        assert!(credentials("Ann", "long enough").check().is_err());
+        assert!(credentials("ann", "short").check().is_err());
+    }
+
+    #[actix_web::test]
+    async fn refuses_wrong_passwords_and_unknown_users() {
+        let app = app!();
+        let created = call_service(&app, post("/register", "ann", "password1").to_request());
+        assert_eq!(created.await.status(), StatusCode::CREATED);
+        for (username, password, status) in [
+            ("ann", "password2", StatusCode::UNAUTHORIZED),
+            ("bob", "password1", StatusCode::UNAUTHORIZED),
+            ("ann", "password1", StatusCode::OK),
+        ] {
+            let response = call_service(&app, post("/login", username, password).to_request());
+            assert_eq!(response.await.status(), status, "{}", username);
+        }
+    }
+
+    #[actix_web::test]
+    async fn logging_out_ends_the_session() {
+        let app = app!();
+        call_service(&app, post("/register", "ann", "password1").to_request()).await;
+        let session: Value =
+            call_and_read_body_json(&app, post("/login", "ann", "password1").to_request()).await;
+        let bearer = format!("Bearer {}", session["token"].as_str().unwrap());
+        let whoami = || {
+            TestRequest::get()
+                .uri("/me")
+                .insert_header((AUTHORIZATION, bearer.clone()))
+                .to_request()
+        };
+        let user: Value = call_and_read_body_json(&app, whoami()).await;
+        assert_eq!(user["username"], "ann");
+
+        let request = TestRequest::post()
+            .uri("/logout")
+            .insert_header((AUTHORIZATION, bearer.clone()))
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            call_service(&app, whoami()).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let unknown = TestRequest::get()
+            .uri("/me")
+            .insert_header((AUTHORIZATION, "Bearer not-a-token"))
+            .to_request();
+        assert_eq!(
+            call_service(&app, unknown).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+}
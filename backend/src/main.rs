@@ -1,30 +1,40 @@
 use actix_web::{get, http::header::ContentType, web, App, HttpResponse, HttpServer};
 
+use auth::{Authenticate, CurrentUser};
+use clap::{Parser, Subcommand};
+use domain::store::{MigrationState, PuzzleStore, Store};
+use domain::users::UserStore;
+use domain::{Difficulty, Puzzle};
 use dotenv::dotenv;
-use serde::{Deserialize, Serialize};
-use sqlx::postgres::PgPoolOptions;
-use sqlx::{Pool, Postgres};
-use std::env;
-use sudoku::Sudoku;
+use pool::PuzzlePool;
+use serde::Deserialize;
+use solver::formats;
+use solver::formats::Format;
+use solver::generate::{generate, GenerateOptions, Symmetry};
+use solver::render;
+use solver::render::RenderOptions;
+use std::sync::Arc;
+use std::{env, io};
 
-#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
-pub struct PuzzleRow {
-    id: String,
-    puzzle: String,
-    solution: String,
-    num_clues: i16,
+mod auth;
+mod pool;
+
+#[derive(Deserialize)]
+pub struct PuzzlesQuery {
+    /// Only puzzles imported from this collection
+    source: Option<String>,
 }
 
 #[get("/puzzles")]
-async fn puzzles(db_pool: web::Data<Pool<Postgres>>) -> HttpResponse {
-    let conn = db_pool.get_ref();
-    if let Ok(rows) = sqlx::query_as!(
-        PuzzleRow,
-        "SELECT id, puzzle, solution, num_clues FROM puzzles"
-    )
-    .fetch_all(conn)
-    .await
-    {
+async fn puzzles(
+    store: web::Data<dyn PuzzleStore>,
+    query: web::Query<PuzzlesQuery>,
+) -> HttpResponse {
+    let rows = match &query.source {
+        Some(source) => store.by_source(source).await,
+        None => store.all().await,
+    };
+    if let Ok(rows) = rows {
         HttpResponse::Ok()
             .content_type(ContentType::json())
             .body(serde_json::to_string(&rows).unwrap())
@@ -32,34 +42,179 @@ async fn puzzles(db_pool: web::Data<Pool<Postgres>>) -> HttpResponse {
         HttpResponse::ServiceUnavailable().finish()
     }
 }
+#[derive(Deserialize)]
+pub struct RandomStoredQuery {
+    difficulty: Option<Difficulty>,
+    /// Only accepted when it is the logged in user, whose seen puzzles are
+    /// left out and who is remembered to have seen this one.
+    player: Option<String>,
+}
+
+/// A stored puzzle picked at random, unlike /randompuzzle which makes one.
+#[get("/puzzles/random")]
+async fn random_stored_puzzle(
+    store: web::Data<dyn PuzzleStore>,
+    user: Option<web::ReqData<CurrentUser>>,
+    query: web::Query<RandomStoredQuery>,
+) -> HttpResponse {
+    let query = query.into_inner();
+    let player = user.map(|u| u.into_inner().0.id);
+    if query.player.is_some() && query.player != player {
+        return HttpResponse::Forbidden().body("Puzzles can only be picked for the logged in user");
+    }
+    match store.random(query.difficulty, player.as_deref()).await {
+        Ok(Some(puzzle)) => {
+            if let Some(player) = &player {
+                if let Err(e) = store.mark_seen(player, &puzzle.id).await {
+                    eprintln!("Could not record that {} saw {}: {}", player, puzzle.id, e);
+                }
+            }
+            HttpResponse::Ok()
+                .content_type(ContentType::json())
+                .body(serde_json::to_string(&puzzle).expect(""))
+        }
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(_) => HttpResponse::ServiceUnavailable().finish(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RandomPuzzleQuery {
+    difficulty: Option<Difficulty>,
+    #[serde(default)]
+    symmetry: Symmetry,
+    seed: Option<u64>,
+    min_clues: Option<usize>,
+    max_clues: Option<usize>,
+    /// Store the puzzle under a new id instead of only returning it
+    #[serde(default)]
+    persist: bool,
+}
+
 #[get("/randompuzzle")]
-async fn random_puzzle() -> HttpResponse {
-    let solution = Sudoku::generate_filled();
-    let puzzle = Sudoku::generate_unique_from(solution);
-    let body = PuzzleRow {
-        id: "generated".to_string(),
-        puzzle: puzzle.to_string(),
-        solution: solution.to_string(),
-        num_clues: 0,
+async fn random_puzzle(
+    store: web::Data<dyn PuzzleStore>,
+    pool: Option<web::Data<PuzzlePool>>,
+    query: web::Query<RandomPuzzleQuery>,
+) -> HttpResponse {
+    let options = GenerateOptions {
+        difficulty: query.difficulty,
+        symmetry: query.symmetry,
+        seed: query.seed,
+        min_clues: query.min_clues,
+        max_clues: query.max_clues,
+    };
+    if let Err(reason) = options.check() {
+        return HttpResponse::BadRequest().body(reason);
+    }
+    // Pooled puzzles are stored already and can only be picked by difficulty
+    let by_difficulty = GenerateOptions {
+        difficulty: options.difficulty,
+        ..GenerateOptions::default()
+    };
+    if let (Some(pool), true) = (pool, options == by_difficulty) {
+        let taken = store.take_unused(options.difficulty).await;
+        pool.taken();
+        match taken {
+            Ok(Some(puzzle)) => {
+                return HttpResponse::Ok()
+                    .content_type(ContentType::json())
+                    .body(serde_json::to_string(&puzzle).expect(""))
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Could not take a puzzle from the pool: {}", e),
+        }
+    }
+    let puzzle = match web::block(move || generate(&options)).await {
+        Ok(Some(puzzle)) => puzzle,
+        Ok(None) => {
+            return HttpResponse::UnprocessableEntity()
+                .body("No puzzle met these options in time, try looser ones")
+        }
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+    let body = if query.persist {
+        // Served as stored, with the creation time the store gave it
+        let stored = match store.insert(&puzzle).await {
+            Ok(true) => store.get(&puzzle.id).await,
+            Ok(false) => return HttpResponse::Conflict().body("This puzzle is stored already"),
+            Err(e) => Err(e),
+        };
+        match stored {
+            Ok(Some(stored)) => stored,
+            Ok(None) => return HttpResponse::InternalServerError().finish(),
+            Err(e) => {
+                eprintln!("Could not store a generated puzzle: {}", e);
+                return HttpResponse::ServiceUnavailable().finish();
+            }
+        }
+    } else {
+        Puzzle {
+            id: "generated".to_string(),
+            ..puzzle
+        }
     };
     HttpResponse::Ok()
         .content_type(ContentType::json())
         .body(serde_json::to_string(&body).expect(""))
 }
+#[derive(Deserialize)]
+pub struct PuzzleQuery {
+    format: Option<Format>,
+}
+
+/// Renders a stored puzzle as plain text in one of the solver's file formats.
+fn puzzle_as_text(row: &Puzzle, format: Format) -> HttpResponse {
+    match row.sudoku() {
+        Ok(sudoku) => HttpResponse::Ok()
+            .content_type(ContentType::plaintext())
+            .body(format!("{}\n", formats::write_puzzle(&sudoku, format))),
+        _ => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SvgQuery {
+    candidates: Option<bool>,
+    solution: Option<bool>,
+}
+
+/// A stored puzzle as a printable SVG, optionally with pencil marks and the
+/// solution drawn beside it.
+#[get("/puzzles/{id}.svg")]
+async fn puzzle_svg(
+    store: web::Data<dyn PuzzleStore>,
+    id: web::Path<(String,)>,
+    query: web::Query<SvgQuery>,
+) -> HttpResponse {
+    let puzzle = store.get(&id.into_inner().0).await;
+    let options = RenderOptions {
+        candidates: query.candidates.unwrap_or(false),
+        solution: query.solution.unwrap_or(false),
+    };
+    match puzzle {
+        Ok(Some(p)) => match p.sudoku() {
+            Ok(sudoku) => HttpResponse::Ok()
+                .content_type("image/svg+xml")
+                .body(render::svg(&sudoku, options)),
+            _ => HttpResponse::InternalServerError().finish(),
+        },
+        Ok(None) => HttpResponse::NotFound().finish(),
+        _ => HttpResponse::InternalServerError().finish(),
+    }
+}
+
 #[get("/puzzles/{id}")]
-async fn get_puzzle(db_pool: web::Data<Pool<Postgres>>, id: web::Path<(String,)>) -> HttpResponse {
-    let puzzle = sqlx::query_as!(
-        PuzzleRow,
-        r#"
-        SELECT id, puzzle, solution, num_clues FROM puzzles WHERE id = $1
-    "#,
-        id.into_inner().0
-    )
-    .fetch_optional(db_pool.get_ref())
-    .await;
+async fn get_puzzle(
+    store: web::Data<dyn PuzzleStore>,
+    id: web::Path<(String,)>,
+    query: web::Query<PuzzleQuery>,
+) -> HttpResponse {
+    let puzzle = store.get(&id.into_inner().0).await;
     match puzzle {
-        Ok(puz) => match puz {
-            Some(p) => HttpResponse::Ok()
+        Ok(puz) => match (puz, query.format) {
+            (Some(p), Some(format)) => puzzle_as_text(&p, format),
+            (Some(p), None) => HttpResponse::Ok()
                 .content_type(ContentType::json())
                 .body(serde_json::to_string(&p).unwrap()),
             _ => HttpResponse::NotFound().finish(),
@@ -68,28 +223,113 @@ async fn get_puzzle(db_pool: web::Data<Pool<Postgres>>, id: web::Path<(String,)>
     }
 }
 
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Serve without applying pending migrations first
+    #[clap(long)]
+    no_migrate: bool,
+
+    /// Keep this many unused puzzles of every difficulty stored, generating
+    /// more in the background, and serve /randompuzzle from them
+    #[clap(long, env = "PUZZLE_POOL_SIZE")]
+    pool_size: Option<u64>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Inspect or apply the database migrations, then exit
+    Migrate {
+        #[clap(subcommand)]
+        action: MigrateAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum MigrateAction {
+    /// List the migrations and whether the database has them
+    Status,
+    /// Apply the pending migrations
+    Run,
+}
+
+async fn migrate(store: &dyn PuzzleStore, action: MigrateAction) -> io::Result<()> {
+    match action {
+        MigrateAction::Status => {
+            let migrations = store.migrations().await.map_err(io::Error::other)?;
+            if migrations.is_empty() {
+                println!("This store has no schema to migrate");
+            }
+            for m in &migrations {
+                println!("{} {:<8} {}", m.version, m.state, m.description);
+            }
+            if migrations
+                .iter()
+                .any(|m| m.state == MigrationState::Unknown)
+            {
+                println!("The database is ahead of this build, upgrade the backend");
+            }
+        }
+        MigrateAction::Run => {
+            store.migrate().await.map_err(io::Error::other)?;
+            println!("Database is up to date");
+        }
+    }
+    Ok(())
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
+    let args = Args::parse();
     let port = env::var("PORT")
         .map(|f| i32::from_str_radix(&f, 10))
         .unwrap_or(Ok(4200))
         .expect("Found a port");
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(
-            env::var("DATABASE_URL")
-                .expect("DATABASE_URL must be set")
-                .as_str(),
-        )
+    let url = env::var("DATABASE_URL")
+        .expect("DATABASE_URL must be set, to memory: for a server without a database");
+    let store: Arc<dyn Store> = domain::store::connect(&url)
         .await
-        .expect("Failed to connect to POSTGRES");
+        .expect("Failed to open the puzzle store")
+        .into();
+    if let Some(Command::Migrate { action }) = args.command {
+        return migrate(store.as_ref(), action).await;
+    }
+    if !args.no_migrate {
+        if let Err(e) = store.migrate().await {
+            eprintln!("Could not migrate the database: {}", e);
+            std::process::exit(1);
+        }
+    }
+    auth::dummy_hash();
+    let pool = args
+        .pool_size
+        .filter(|&size| size > 0)
+        .map(|size| web::Data::new(PuzzlePool::spawn(store.clone(), size)));
     HttpServer::new(move || {
-        App::new()
-            .app_data(web::Data::new(pool.clone()))
+        let puzzle_store: Arc<dyn PuzzleStore> = store.clone();
+        let user_store: Arc<dyn UserStore> = store.clone();
+        let app = App::new()
+            .app_data(web::Data::from(puzzle_store))
+            .app_data(web::Data::from(user_store))
+            .wrap(Authenticate)
+            .service(auth::register)
+            .service(auth::login)
+            .service(auth::logout)
+            .service(auth::me)
             .service(puzzles)
+            // Before /puzzles/{id}, which would take "random" for an id
+            .service(random_stored_puzzle)
+            .service(puzzle_svg)
             .service(get_puzzle)
-            .service(random_puzzle)
+            .service(random_puzzle);
+        match &pool {
+            Some(pool) => app.app_data(pool.clone()),
+            None => app,
+        }
     })
     .bind(format!("0.0.0.0:{}", port))?
     .run()
@@ -0,0 +1,107 @@
+use actix_web::rt;
+use domain::store::{PuzzleStore, StoreError};
+use domain::{Difficulty, Puzzle};
+use solver::solver::generate_puzzle;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Puzzles generated per trip to a blocking thread. Hard puzzles are rare,
+/// so most of each round is thrown away once the other levels are full.
+const ROUND: usize = 100;
+
+/// How long the pool waits for a puzzle to be taken before checking anyway.
+const CHECK_EVERY: Duration = Duration::from_secs(60);
+
+/// Handle to a background task that keeps generated puzzles ready to serve,
+/// so requests do not have to wait for the generator.
+pub struct PuzzlePool {
+    wake: Arc<Notify>,
+}
+
+impl PuzzlePool {
+    /// Starts the task, which keeps at least `size` unused puzzles of every
+    /// difficulty in `store`.
+    pub fn spawn(store: Arc<dyn PuzzleStore>, size: u64) -> Self {
+        let wake = Arc::new(Notify::new());
+        let woken = wake.clone();
+        rt::spawn(async move {
+            loop {
+                if let Err(e) = top_up(store.as_ref(), size).await {
+                    eprintln!("Could not top up the puzzle pool: {}", e);
+                }
+                let _ = rt::time::timeout(CHECK_EVERY, woken.notified()).await;
+            }
+        });
+        PuzzlePool { wake }
+    }
+
+    /// Tells the task a puzzle was taken, or that the pool ran dry.
+    pub fn taken(&self) {
+        self.wake.notify_one();
+    }
+}
+
+/// Generates puzzles until `store` has `size` unused ones of every
+/// difficulty. The pool is counted again after every round, as puzzles the
+/// store already has are not inserted twice.
+async fn top_up(store: &dyn PuzzleStore, size: u64) -> Result<(), StoreError> {
+    loop {
+        let mut missing = HashMap::new();
+        for difficulty in Difficulty::ALL {
+            let unused = store.count_unused(difficulty).await?;
+            missing.insert(difficulty, size.saturating_sub(unused));
+        }
+        if missing.values().all(|&n| n == 0) {
+            return Ok(());
+        }
+        let puzzles = rt::task::spawn_blocking(move || generate(missing))
+            .await
+            .expect("Generator thread panicked");
+        store.insert_many(&puzzles).await?;
+    }
+}
+
+/// Generates up to a round of puzzles and keeps the ones the pool is short
+/// of, stopping as soon as it has them all.
+fn generate(mut missing: HashMap<Difficulty, u64>) -> Vec<Puzzle> {
+    let total = missing.values().sum::<u64>() as usize;
+    (0..ROUND)
+        .map(|_| generate_puzzle())
+        .filter(|p| match difficulty(p).and_then(|d| missing.get_mut(&d)) {
+            Some(n) if *n > 0 => {
+                *n -= 1;
+                true
+            }
+            _ => false,
+        })
+        .take(total)
+        .collect()
+}
+
+fn difficulty(puzzle: &Puzzle) -> Option<Difficulty> {
+    puzzle.difficulty.as_deref().and_then(|d| d.parse().ok())
+}
+
+#[cfg(test)]
+mod test {
+    use super::top_up;
+    use domain::memory::MemoryStore;
+    use domain::store::PuzzleStore;
+    use domain::Difficulty;
+
+    #[actix_web::test]
+    async fn tops_up_every_difficulty() {
+        let store = MemoryStore::default();
+        top_up(&store, 2).await.unwrap();
+        for difficulty in Difficulty::ALL {
+            assert_eq!(store.count_unused(difficulty).await.unwrap(), 2);
+        }
+        store.take_unused(Some(Difficulty::ALL[0])).await.unwrap();
+        top_up(&store, 2).await.unwrap();
+        for difficulty in Difficulty::ALL {
+            assert_eq!(store.count_unused(difficulty).await.unwrap(), 2);
+        }
+    }
+}
@@ -0,0 +1,6 @@
+DROP INDEX puzzles_source_idx;
+ALTER TABLE puzzles
+    DROP COLUMN source,
+    DROP COLUMN source_line,
+    DROP COLUMN author,
+    DROP COLUMN source_url;
@@ -0,0 +1,7 @@
+-- Where imported puzzles came from, collections differ in licence
+ALTER TABLE puzzles
+    ADD COLUMN source text,
+    ADD COLUMN source_line integer,
+    ADD COLUMN author text,
+    ADD COLUMN source_url text;
+CREATE INDEX puzzles_source_idx ON puzzles (source);
@@ -0,0 +1,4 @@
+DROP TABLE seen_puzzles;
+DROP INDEX puzzles_difficulty_random_idx;
+DROP INDEX puzzles_random_idx;
+ALTER TABLE puzzles DROP COLUMN random_key;
@@ -0,0 +1,13 @@
+-- Random picks seek to a random point in this key instead of sorting the
+-- table, every existing row gets its own value from the volatile default
+ALTER TABLE puzzles ADD COLUMN random_key double precision NOT NULL DEFAULT random();
+CREATE INDEX puzzles_random_idx ON puzzles (random_key);
+CREATE INDEX puzzles_difficulty_random_idx ON puzzles (difficulty, random_key);
+
+-- Puzzles each player has been shown, left out of their random picks
+CREATE TABLE seen_puzzles (
+    player text NOT NULL,
+    puzzle_id text NOT NULL REFERENCES puzzles (id) ON DELETE CASCADE,
+    seen_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    PRIMARY KEY (player, puzzle_id)
+);
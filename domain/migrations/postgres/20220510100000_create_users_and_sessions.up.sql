@@ -0,0 +1,16 @@
+CREATE TABLE users (
+    id text PRIMARY KEY NOT NULL,
+    username text UNIQUE NOT NULL,
+    password_hash text NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+-- Only a hash of each session token is kept, so the table cannot be used to
+-- log in
+CREATE TABLE sessions (
+    token_hash text PRIMARY KEY NOT NULL,
+    user_id text NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+CREATE INDEX sessions_user_idx ON sessions (user_id);
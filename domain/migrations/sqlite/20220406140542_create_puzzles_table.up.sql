@@ -0,0 +1,8 @@
+-- Add up migration script here
+CREATE TABLE puzzles (
+    id TEXT PRIMARY KEY NOT NULL,
+    puzzle TEXT UNIQUE NOT NULL,
+    solution TEXT NOT NULL,
+    num_clues INTEGER NOT NULL,
+    created_at TEXT DEFAULT CURRENT_TIMESTAMP
+);
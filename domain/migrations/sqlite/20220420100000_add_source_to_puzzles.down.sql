@@ -0,0 +1,5 @@
+DROP INDEX puzzles_source_idx;
+ALTER TABLE puzzles DROP COLUMN source;
+ALTER TABLE puzzles DROP COLUMN source_line;
+ALTER TABLE puzzles DROP COLUMN author;
+ALTER TABLE puzzles DROP COLUMN source_url;
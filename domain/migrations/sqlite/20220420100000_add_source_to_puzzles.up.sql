@@ -0,0 +1,6 @@
+-- Where imported puzzles came from, collections differ in licence
+ALTER TABLE puzzles ADD COLUMN source TEXT;
+ALTER TABLE puzzles ADD COLUMN source_line INTEGER;
+ALTER TABLE puzzles ADD COLUMN author TEXT;
+ALTER TABLE puzzles ADD COLUMN source_url TEXT;
+CREATE INDEX puzzles_source_idx ON puzzles (source);
@@ -0,0 +1,2 @@
+DROP INDEX puzzles_unused_idx;
+ALTER TABLE puzzles DROP COLUMN used_at;
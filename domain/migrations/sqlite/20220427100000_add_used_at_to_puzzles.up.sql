@@ -0,0 +1,3 @@
+-- Set when the puzzle pool hands a puzzle out
+ALTER TABLE puzzles ADD COLUMN used_at TEXT;
+CREATE INDEX puzzles_unused_idx ON puzzles (difficulty, created_at) WHERE used_at IS NULL;
@@ -0,0 +1,5 @@
+DROP TABLE seen_puzzles;
+DROP INDEX puzzles_difficulty_random_idx;
+DROP INDEX puzzles_random_idx;
+DROP TRIGGER puzzles_random_key;
+ALTER TABLE puzzles DROP COLUMN random_key;
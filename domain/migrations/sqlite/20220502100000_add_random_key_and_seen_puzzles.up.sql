@@ -0,0 +1,20 @@
+-- Random picks seek to a random point in this key instead of sorting the
+-- table. SQLite cannot add a column with a volatile default, so existing
+-- rows are filled in here and new ones by the trigger.
+ALTER TABLE puzzles ADD COLUMN random_key REAL NOT NULL DEFAULT 0;
+UPDATE puzzles SET random_key = (random() / 18446744073709551616.0) + 0.5;
+CREATE TRIGGER puzzles_random_key AFTER INSERT ON puzzles
+BEGIN
+    UPDATE puzzles SET random_key = (random() / 18446744073709551616.0) + 0.5
+    WHERE id = NEW.id;
+END;
+CREATE INDEX puzzles_random_idx ON puzzles (random_key);
+CREATE INDEX puzzles_difficulty_random_idx ON puzzles (difficulty, random_key);
+
+-- Puzzles each player has been shown, left out of their random picks
+CREATE TABLE seen_puzzles (
+    player TEXT NOT NULL,
+    puzzle_id TEXT NOT NULL REFERENCES puzzles (id) ON DELETE CASCADE,
+    seen_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (player, puzzle_id)
+);
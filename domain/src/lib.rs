@@ -0,0 +1,246 @@
+pub mod memory;
+#[cfg(feature = "sqlx")]
+mod migrations;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod store;
+pub mod users;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use sudoku::Sudoku;
+
+/// A puzzle as stored in the `puzzles` table and served by the backend.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Puzzle {
+    pub id: String,
+    /// The puzzle as an 81 character line, with `.` for empty cells
+    pub puzzle: String,
+    /// The solution as an 81 character line
+    pub solution: String,
+    pub num_clues: i16,
+    #[serde(default)]
+    pub difficulty: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// Collection an imported puzzle came from, such as `top95`
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Line of the puzzle in the file it was imported from
+    #[serde(default)]
+    pub source_line: Option<i32>,
+    #[serde(default)]
+    pub author: Option<String>,
+    /// Where the collection was published
+    #[serde(default)]
+    pub source_url: Option<String>,
+}
+
+/// The columns of the `puzzles` table, in the order of [`Puzzle`]'s fields.
+#[cfg(feature = "sqlx")]
+pub(crate) const COLUMNS: &str = "id, puzzle, solution, num_clues, difficulty, created_at, \
+    source, source_line, author, source_url";
+
+/// How hard a puzzle is for a person, by the hardest technique solving it
+/// takes. Stored in the `difficulty` column by name.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    /// Singles only
+    Easy,
+    /// Locked candidates and pairs
+    Medium,
+    /// Triples, quads and fish
+    Hard,
+    /// Needs more than any of those
+    Expert,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Expert,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+            Difficulty::Expert => "expert",
+        }
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Difficulty::ALL
+            .into_iter()
+            .find(|d| d.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                format!(
+                    "unknown difficulty '{}', expected easy, medium, hard or expert",
+                    s
+                )
+            })
+    }
+}
+
+/// Why a puzzle or a stored row is not usable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PuzzleError {
+    /// The named field is not a sudoku
+    Parse { field: &'static str, reason: String },
+    /// The puzzle has no solution, or more than one
+    NoUniqueSolution,
+    /// The solution does not solve the puzzle
+    WrongSolution,
+    /// The stored clue count disagrees with the puzzle
+    ClueCount { stored: i16, actual: i16 },
+}
+
+impl Display for PuzzleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PuzzleError::Parse { field, reason } => {
+                write!(f, "{} is not a sudoku: {}", field, reason)
+            }
+            PuzzleError::NoUniqueSolution => write!(f, "puzzle has no unique solution"),
+            PuzzleError::WrongSolution => write!(f, "solution does not match the puzzle"),
+            PuzzleError::ClueCount { stored, actual } => {
+                write!(f, "puzzle has {} clues but {} are recorded", actual, stored)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
+
+/// Parses one field of a puzzle row, which must be an 81 character line.
+pub fn parse(field: &'static str, line: &str) -> Result<Sudoku, PuzzleError> {
+    Sudoku::from_str_line(line).map_err(|e| PuzzleError::Parse {
+        field,
+        reason: e.to_string(),
+    })
+}
+
+/// Number of filled cells, the way every binary should count them.
+pub fn clue_count(sudoku: &Sudoku) -> i16 {
+    sudoku.n_clues() as i16
+}
+
+impl Puzzle {
+    /// A new row with a fresh id for `sudoku` and the solution given.
+    pub fn with_solution(sudoku: &Sudoku, solution: &Sudoku) -> Self {
+        Puzzle {
+            id: ulid::Ulid::new().to_string(),
+            puzzle: sudoku.to_string(),
+            solution: solution.to_string(),
+            num_clues: clue_count(sudoku),
+            difficulty: None,
+            created_at: None,
+            source: None,
+            source_line: None,
+            author: None,
+            source_url: None,
+        }
+    }
+
+    /// A new row with a fresh id for `sudoku`, which must have a unique
+    /// solution.
+    pub fn from_sudoku(sudoku: &Sudoku) -> Result<Self, PuzzleError> {
+        let solution = sudoku.solve_unique().ok_or(PuzzleError::NoUniqueSolution)?;
+        Ok(Puzzle::with_solution(sudoku, &solution))
+    }
+
+    pub fn sudoku(&self) -> Result<Sudoku, PuzzleError> {
+        parse("puzzle", &self.puzzle)
+    }
+
+    pub fn solution_sudoku(&self) -> Result<Sudoku, PuzzleError> {
+        parse("solution", &self.solution)
+    }
+
+    /// Checks that the row holds a puzzle with a unique solution, that the
+    /// stored solution is it, and that the clue count is right.
+    pub fn validate(&self) -> Result<(), PuzzleError> {
+        let sudoku = self.sudoku()?;
+        let solution = self.solution_sudoku()?;
+        match sudoku.solve_unique() {
+            None => return Err(PuzzleError::NoUniqueSolution),
+            Some(s) if s != solution => return Err(PuzzleError::WrongSolution),
+            _ => {}
+        }
+        let actual = clue_count(&sudoku);
+        if actual != self.num_clues {
+            return Err(PuzzleError::ClueCount {
+                stored: self.num_clues,
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Display for Puzzle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Puzzle(id: {}, puzzle: {}, solution: {}, num_clues: {})",
+            &self.id, &self.puzzle, &self.solution, &self.num_clues
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Difficulty, Puzzle, PuzzleError};
+    use sudoku::Sudoku;
+
+    const LINE: &str =
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..";
+
+    #[test]
+    fn new_rows_validate() {
+        let puzzle = Puzzle::from_sudoku(&Sudoku::from_str_line(LINE).unwrap()).unwrap();
+        assert_eq!(puzzle.num_clues, 32);
+        assert_eq!(puzzle.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_catches_a_wrong_clue_count() {
+        let mut puzzle = Puzzle::from_sudoku(&Sudoku::from_str_line(LINE).unwrap()).unwrap();
+        puzzle.num_clues = 81 - puzzle.num_clues;
+        assert_eq!(
+            puzzle.validate(),
+            Err(PuzzleError::ClueCount {
+                stored: 49,
+                actual: 32
+            })
+        );
+    }
+
+    #[test]
+    fn difficulties_round_trip_by_name() {
+        for difficulty in Difficulty::ALL {
+            assert_eq!(difficulty.to_string().parse(), Ok(difficulty));
+        }
+        assert_eq!("Hard".parse(), Ok(Difficulty::Hard));
+        assert!("fiendish".parse::<Difficulty>().is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use crate::store::{PuzzleStore, StoreError};
+use crate::users::{User, UserStore};
+use crate::{Difficulty, Puzzle};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::seq::SliceRandom;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+/// Keeps puzzles and accounts in memory, for running the server without a database and
+/// for tests.
+#[derive(Default)]
+pub struct MemoryStore {
+    puzzles: Mutex<Vec<Puzzle>>,
+    /// Ids of the puzzles the pool has handed out
+    used: Mutex<HashSet<String>>,
+    /// Pairs of player and puzzle id
+    seen: Mutex<HashSet<(String, String)>>,
+    /// Accounts with their password hashes
+    users: Mutex<Vec<(User, String)>>,
+    /// User id and expiry by token hash
+    sessions: Mutex<HashMap<String, (String, DateTime<Utc>)>>,
+}
+
+#[async_trait]
+impl PuzzleStore for MemoryStore {
+    async fn all(&self) -> Result<Vec<Puzzle>, StoreError> {
+        let mut puzzles = self.puzzles.lock().expect("Store lock poisoned").clone();
+        puzzles.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+        Ok(puzzles)
+    }
+
+    async fn page(&self, after: Option<&str>, limit: u32) -> Result<Vec<Puzzle>, StoreError> {
+        let after = after.unwrap_or_default();
+        let puzzles = self.puzzles.lock().expect("Store lock poisoned");
+        let mut page: Vec<Puzzle> = puzzles
+            .iter()
+            .filter(|p| p.id.as_str() > after)
+            .cloned()
+            .collect();
+        page.sort_by(|a, b| a.id.cmp(&b.id));
+        page.truncate(limit as usize);
+        Ok(page)
+    }
+
+    async fn by_source(&self, source: &str) -> Result<Vec<Puzzle>, StoreError> {
+        let mut puzzles = self.all().await?;
+        puzzles.retain(|p| p.source.as_deref() == Some(source));
+        Ok(puzzles)
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Puzzle>, StoreError> {
+        let puzzles = self.puzzles.lock().expect("Store lock poisoned");
+        Ok(puzzles.iter().find(|p| p.id == id).cloned())
+    }
+
+    async fn insert(&self, puzzle: &Puzzle) -> Result<bool, StoreError> {
+        let mut puzzles = self.puzzles.lock().expect("Store lock poisoned");
+        if puzzles
+            .iter()
+            .any(|p| p.id == puzzle.id || p.puzzle == puzzle.puzzle)
+        {
+            return Ok(false);
+        }
+        puzzles.push(Puzzle {
+            created_at: puzzle.created_at.or_else(|| Some(Utc::now())),
+            ..puzzle.clone()
+        });
+        Ok(true)
+    }
+
+    async fn count_unused(&self, difficulty: Difficulty) -> Result<u64, StoreError> {
+        let puzzles = self.puzzles.lock().expect("Store lock poisoned");
+        let used = self.used.lock().expect("Store lock poisoned");
+        Ok(puzzles
+            .iter()
+            .filter(|p| p.difficulty.as_deref() == Some(difficulty.name()) && !used.contains(&p.id))
+            .count() as u64)
+    }
+
+    async fn take_unused(
+        &self,
+        difficulty: Option<Difficulty>,
+    ) -> Result<Option<Puzzle>, StoreError> {
+        let puzzles = self.puzzles.lock().expect("Store lock poisoned");
+        let mut used = self.used.lock().expect("Store lock poisoned");
+        let oldest = puzzles
+            .iter()
+            .filter(|p| {
+                difficulty.is_none_or(|d| p.difficulty.as_deref() == Some(d.name()))
+                    && !used.contains(&p.id)
+            })
+            .min_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)))
+            .cloned();
+        if let Some(puzzle) = &oldest {
+            used.insert(puzzle.id.clone());
+        }
+        Ok(oldest)
+    }
+
+    async fn random(
+        &self,
+        difficulty: Option<Difficulty>,
+        unseen_by: Option<&str>,
+    ) -> Result<Option<Puzzle>, StoreError> {
+        let puzzles = self.puzzles.lock().expect("Store lock poisoned");
+        let seen = self.seen.lock().expect("Store lock poisoned");
+        let candidates: Vec<&Puzzle> = puzzles
+            .iter()
+            .filter(|p| difficulty.is_none_or(|d| p.difficulty.as_deref() == Some(d.name())))
+            .filter(|p| {
+                unseen_by.is_none_or(|player| !seen.contains(&(player.to_string(), p.id.clone())))
+            })
+            .collect();
+        Ok(candidates
+            .choose(&mut rand::thread_rng())
+            .map(|&p| p.clone()))
+    }
+
+    async fn mark_seen(&self, player: &str, puzzle_id: &str) -> Result<(), StoreError> {
+        let mut seen = self.seen.lock().expect("Store lock poisoned");
+        seen.insert((player.to_string(), puzzle_id.to_string()));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserStore for MemoryStore {
+    async fn create_user(&self, user: &User, password_hash: &str) -> Result<bool, StoreError> {
+        let mut users = self.users.lock().expect("Store lock poisoned");
+        if users
+            .iter()
+            .any(|(u, _)| u.id == user.id || u.username == user.username)
+        {
+            return Ok(false);
+        }
+        let user = User {
+            created_at: user.created_at.or_else(|| Some(Utc::now())),
+            ..user.clone()
+        };
+        users.push((user, password_hash.to_string()));
+        Ok(true)
+    }
+
+    async fn user_by_name(&self, username: &str) -> Result<Option<(User, String)>, StoreError> {
+        let users = self.users.lock().expect("Store lock poisoned");
+        Ok(users.iter().find(|(u, _)| u.username == username).cloned())
+    }
+
+    async fn create_session(
+        &self,
+        token_hash: &str,
+        user_id: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), StoreError> {
+        let mut sessions = self.sessions.lock().expect("Store lock poisoned");
+        let now = Utc::now();
+        sessions.retain(|_, (_, expires_at)| *expires_at > now);
+        sessions.insert(token_hash.to_string(), (user_id.to_string(), expires_at));
+        Ok(())
+    }
+
+    async fn session_user(&self, token_hash: &str) -> Result<Option<User>, StoreError> {
+        let sessions = self.sessions.lock().expect("Store lock poisoned");
+        let user_id = match sessions.get(token_hash) {
+            Some((user_id, expires_at)) if *expires_at > Utc::now() => user_id,
+            _ => return Ok(None),
+        };
+        let users = self.users.lock().expect("Store lock poisoned");
+        Ok(users
+            .iter()
+            .find(|(u, _)| &u.id == user_id)
+            .map(|(u, _)| u.clone()))
+    }
+
+    async fn delete_session(&self, token_hash: &str) -> Result<(), StoreError> {
+        let mut sessions = self.sessions.lock().expect("Store lock poisoned");
+        sessions.remove(token_hash);
+        Ok(())
+    }
+}
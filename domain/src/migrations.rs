@@ -0,0 +1,60 @@
+use crate::store::{Migration, MigrationState, StoreError};
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::Acquire;
+use std::collections::BTreeMap;
+use std::ops::Deref;
+
+/// Compares the migrations embedded in this build with the ones applied to
+/// the database.
+pub(crate) async fn status<'a, A>(migrator: &Migrator, db: A) -> Result<Vec<Migration>, StoreError>
+where
+    A: Acquire<'a>,
+    <A::Connection as Deref>::Target: Migrate,
+{
+    let mut conn = db.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let mut applied: BTreeMap<i64, _> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| (m.version, m.checksum))
+        .collect();
+    let mut migrations: Vec<Migration> = migrator
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| Migration {
+            version: m.version,
+            description: m.description.to_string(),
+            state: match applied.remove(&m.version) {
+                None => MigrationState::Pending,
+                Some(checksum) if checksum != m.checksum => MigrationState::Modified,
+                Some(_) => MigrationState::Applied,
+            },
+        })
+        .collect();
+    migrations.extend(applied.into_keys().map(|version| Migration {
+        version,
+        description: String::new(),
+        state: MigrationState::Unknown,
+    }));
+    migrations.sort_by_key(|m| m.version);
+    Ok(migrations)
+}
+
+/// Runs the pending migrations, unless the database is ahead of this build.
+pub(crate) async fn run<'a, A>(migrator: &Migrator, db: A) -> Result<(), StoreError>
+where
+    A: Acquire<'a> + Copy,
+    <A::Connection as Deref>::Target: Migrate,
+{
+    let ahead: Vec<i64> = status(migrator, db)
+        .await?
+        .into_iter()
+        .filter(|m| m.state == MigrationState::Unknown)
+        .map(|m| m.version)
+        .collect();
+    if !ahead.is_empty() {
+        return Err(StoreError::SchemaAhead(ahead));
+    }
+    Ok(migrator.run(db).await?)
+}
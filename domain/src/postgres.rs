@@ -0,0 +1,269 @@
+use crate::migrations;
+use crate::store::{Migration, PuzzleStore, StoreError};
+use crate::users::{User, UserStore};
+use crate::{Difficulty, Puzzle, COLUMNS};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::migrate::Migrator;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::{Pool, Postgres};
+
+static MIGRATOR: Migrator = sqlx::migrate!("migrations/postgres");
+
+/// The `puzzles` table of a Postgres database.
+pub struct PgStore {
+    pool: Pool<Postgres>,
+}
+
+impl PgStore {
+    pub async fn connect(url: &str) -> Result<Self, StoreError> {
+        let pool = PgPoolOptions::new().max_connections(5).connect(url).await?;
+        Ok(PgStore { pool })
+    }
+}
+
+#[async_trait]
+impl PuzzleStore for PgStore {
+    async fn all(&self) -> Result<Vec<Puzzle>, StoreError> {
+        let query = format!("SELECT {} FROM puzzles ORDER BY created_at, id", COLUMNS);
+        Ok(sqlx::query_as::<_, Puzzle>(&query)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn page(&self, after: Option<&str>, limit: u32) -> Result<Vec<Puzzle>, StoreError> {
+        let query = format!(
+            "SELECT {} FROM puzzles WHERE id > $1 ORDER BY id LIMIT $2",
+            COLUMNS
+        );
+        Ok(sqlx::query_as::<_, Puzzle>(&query)
+            .bind(after.unwrap_or_default())
+            .bind(i64::from(limit))
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn by_source(&self, source: &str) -> Result<Vec<Puzzle>, StoreError> {
+        let query = format!(
+            "SELECT {} FROM puzzles WHERE source = $1 ORDER BY created_at, id",
+            COLUMNS
+        );
+        Ok(sqlx::query_as::<_, Puzzle>(&query)
+            .bind(source)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Puzzle>, StoreError> {
+        let query = format!("SELECT {} FROM puzzles WHERE id = $1", COLUMNS);
+        Ok(sqlx::query_as::<_, Puzzle>(&query)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn insert(&self, puzzle: &Puzzle) -> Result<bool, StoreError> {
+        let query = format!(
+            "INSERT INTO puzzles ({}) VALUES ($1, $2, $3, $4, $5, COALESCE($6, now()), $7, $8, $9, $10) ON CONFLICT DO NOTHING",
+            COLUMNS
+        );
+        let result = sqlx::query(&query)
+            .bind(&puzzle.id)
+            .bind(&puzzle.puzzle)
+            .bind(&puzzle.solution)
+            .bind(puzzle.num_clues)
+            .bind(&puzzle.difficulty)
+            .bind(puzzle.created_at)
+            .bind(&puzzle.source)
+            .bind(puzzle.source_line)
+            .bind(&puzzle.author)
+            .bind(&puzzle.source_url)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn insert_many(&self, puzzles: &[Puzzle]) -> Result<u64, StoreError> {
+        // One statement for the lot, with a column array per field
+        let query = format!(
+            "INSERT INTO puzzles ({columns}) \
+             SELECT id, puzzle, solution, num_clues, difficulty, COALESCE(created_at, now()), \
+                    source, source_line, author, source_url \
+             FROM UNNEST($1::text[], $2::text[], $3::text[], $4::smallint[], $5::text[], \
+                         $6::timestamptz[], $7::text[], $8::integer[], $9::text[], $10::text[]) \
+             AS t({columns}) \
+             ON CONFLICT DO NOTHING",
+            columns = COLUMNS
+        );
+        let column = |field: fn(&Puzzle) -> Option<&str>| -> Vec<Option<&str>> {
+            puzzles.iter().map(field).collect()
+        };
+        let result = sqlx::query(&query)
+            .bind(column(|p| Some(&p.id)))
+            .bind(column(|p| Some(&p.puzzle)))
+            .bind(column(|p| Some(&p.solution)))
+            .bind(puzzles.iter().map(|p| p.num_clues).collect::<Vec<_>>())
+            .bind(column(|p| p.difficulty.as_deref()))
+            .bind(puzzles.iter().map(|p| p.created_at).collect::<Vec<_>>())
+            .bind(column(|p| p.source.as_deref()))
+            .bind(puzzles.iter().map(|p| p.source_line).collect::<Vec<_>>())
+            .bind(column(|p| p.author.as_deref()))
+            .bind(column(|p| p.source_url.as_deref()))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn count_unused(&self, difficulty: Difficulty) -> Result<u64, StoreError> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT count(*) FROM puzzles WHERE difficulty = $1 AND used_at IS NULL",
+        )
+        .bind(difficulty.name())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count as u64)
+    }
+
+    async fn take_unused(
+        &self,
+        difficulty: Option<Difficulty>,
+    ) -> Result<Option<Puzzle>, StoreError> {
+        // SKIP LOCKED lets concurrent requests each claim a different row
+        let query = format!(
+            "UPDATE puzzles SET used_at = now() WHERE id = (\
+                 SELECT id FROM puzzles \
+                 WHERE used_at IS NULL AND ($1::text IS NULL OR difficulty = $1) \
+                 ORDER BY created_at, id LIMIT 1 FOR UPDATE SKIP LOCKED) \
+             RETURNING {}",
+            COLUMNS
+        );
+        Ok(sqlx::query_as::<_, Puzzle>(&query)
+            .bind(difficulty.map(|d| d.name()))
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn random(
+        &self,
+        difficulty: Option<Difficulty>,
+        unseen_by: Option<&str>,
+    ) -> Result<Option<Puzzle>, StoreError> {
+        let query = format!(
+            "SELECT {} FROM puzzles WHERE random_key >= $1 {} \
+             AND NOT EXISTS (SELECT 1 FROM seen_puzzles s \
+                             WHERE s.player = $2 AND s.puzzle_id = puzzles.id) \
+             ORDER BY random_key LIMIT 1",
+            COLUMNS,
+            if difficulty.is_some() {
+                "AND difficulty = $3"
+            } else {
+                ""
+            }
+        );
+        // Seek from a random key along the index, wrapping round to the
+        // start when nothing lies past it
+        for pivot in [rand::random::<f64>(), 0.0] {
+            let mut pick = sqlx::query_as::<_, Puzzle>(&query)
+                .bind(pivot)
+                .bind(unseen_by);
+            if let Some(difficulty) = difficulty {
+                pick = pick.bind(difficulty.name());
+            }
+            if let Some(puzzle) = pick.fetch_optional(&self.pool).await? {
+                return Ok(Some(puzzle));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn mark_seen(&self, player: &str, puzzle_id: &str) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO seen_puzzles (player, puzzle_id) VALUES ($1, $2) \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(player)
+        .bind(puzzle_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn migrate(&self) -> Result<(), StoreError> {
+        migrations::run(&MIGRATOR, &self.pool).await
+    }
+
+    async fn migrations(&self) -> Result<Vec<Migration>, StoreError> {
+        migrations::status(&MIGRATOR, &self.pool).await
+    }
+}
+
+#[async_trait]
+impl UserStore for PgStore {
+    async fn create_user(&self, user: &User, password_hash: &str) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "INSERT INTO users (id, username, password_hash, created_at) \
+             VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING",
+        )
+        .bind(&user.id)
+        .bind(&user.username)
+        .bind(password_hash)
+        .bind(user.created_at.unwrap_or_else(Utc::now))
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn user_by_name(&self, username: &str) -> Result<Option<(User, String)>, StoreError> {
+        let row: Option<(String, String, DateTime<Utc>, String)> = sqlx::query_as(
+            "SELECT id, username, created_at, password_hash FROM users WHERE username = $1",
+        )
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|(id, username, created_at, hash)| {
+            let user = User {
+                id,
+                username,
+                created_at: Some(created_at),
+            };
+            (user, hash)
+        }))
+    }
+
+    async fn create_session(
+        &self,
+        token_hash: &str,
+        user_id: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), StoreError> {
+        sqlx::query("DELETE FROM sessions WHERE expires_at <= now()")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("INSERT INTO sessions (token_hash, user_id, expires_at) VALUES ($1, $2, $3)")
+            .bind(token_hash)
+            .bind(user_id)
+            .bind(expires_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn session_user(&self, token_hash: &str) -> Result<Option<User>, StoreError> {
+        Ok(sqlx::query_as::<_, User>(
+            "SELECT users.id, users.username, users.created_at FROM sessions \
+             JOIN users ON users.id = sessions.user_id \
+             WHERE sessions.token_hash = $1 AND sessions.expires_at > now()",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    async fn delete_session(&self, token_hash: &str) -> Result<(), StoreError> {
+        sqlx::query("DELETE FROM sessions WHERE token_hash = $1")
+            .bind(token_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
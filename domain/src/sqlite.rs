@@ -0,0 +1,330 @@
+use crate::migrations;
+use crate::store::{Migration, PuzzleStore, StoreError};
+use crate::users::{User, UserStore};
+use crate::{Difficulty, Puzzle, COLUMNS};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::migrate::Migrator;
+use sqlx::query::Query;
+use sqlx::sqlite::{SqliteArguments, SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::{Pool, Sqlite};
+use std::str::FromStr;
+
+/// The Postgres migrations in SQLite's dialect, under the same versions.
+static MIGRATOR: Migrator = sqlx::migrate!("migrations/sqlite");
+
+/// Puzzles in a SQLite database, which is created empty when missing.
+pub struct SqliteStore {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteStore {
+    pub async fn connect(url: &str) -> Result<Self, StoreError> {
+        let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+        // Every connection to `sqlite::memory:` opens a database of its own
+        let connections = if url.contains(":memory:") { 1 } else { 5 };
+        let pool = SqlitePoolOptions::new()
+            .max_connections(connections)
+            .connect_with(options)
+            .await?;
+        Ok(SqliteStore { pool })
+    }
+}
+
+fn insert_sql() -> String {
+    format!(
+        "INSERT INTO puzzles ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?) ON CONFLICT DO NOTHING",
+        COLUMNS
+    )
+}
+
+fn bind_insert<'q>(sql: &'q str, puzzle: &'q Puzzle) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+    sqlx::query(sql)
+        .bind(&puzzle.id)
+        .bind(&puzzle.puzzle)
+        .bind(&puzzle.solution)
+        .bind(puzzle.num_clues)
+        .bind(&puzzle.difficulty)
+        .bind(puzzle.created_at.unwrap_or_else(Utc::now))
+        .bind(&puzzle.source)
+        .bind(puzzle.source_line)
+        .bind(&puzzle.author)
+        .bind(&puzzle.source_url)
+}
+
+#[async_trait]
+impl PuzzleStore for SqliteStore {
+    async fn all(&self) -> Result<Vec<Puzzle>, StoreError> {
+        let query = format!("SELECT {} FROM puzzles ORDER BY created_at, id", COLUMNS);
+        Ok(sqlx::query_as::<_, Puzzle>(&query)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn page(&self, after: Option<&str>, limit: u32) -> Result<Vec<Puzzle>, StoreError> {
+        let query = format!(
+            "SELECT {} FROM puzzles WHERE id > ? ORDER BY id LIMIT ?",
+            COLUMNS
+        );
+        Ok(sqlx::query_as::<_, Puzzle>(&query)
+            .bind(after.unwrap_or_default())
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn by_source(&self, source: &str) -> Result<Vec<Puzzle>, StoreError> {
+        let query = format!(
+            "SELECT {} FROM puzzles WHERE source = ? ORDER BY created_at, id",
+            COLUMNS
+        );
+        Ok(sqlx::query_as::<_, Puzzle>(&query)
+            .bind(source)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Puzzle>, StoreError> {
+        let query = format!("SELECT {} FROM puzzles WHERE id = ?", COLUMNS);
+        Ok(sqlx::query_as::<_, Puzzle>(&query)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn insert(&self, puzzle: &Puzzle) -> Result<bool, StoreError> {
+        let sql = insert_sql();
+        let result = bind_insert(&sql, puzzle).execute(&self.pool).await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn insert_many(&self, puzzles: &[Puzzle]) -> Result<u64, StoreError> {
+        let sql = insert_sql();
+        let mut tx = self.pool.begin().await?;
+        let mut inserted = 0;
+        for puzzle in puzzles {
+            inserted += bind_insert(&sql, puzzle)
+                .execute(&mut tx)
+                .await?
+                .rows_affected();
+        }
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
+    async fn count_unused(&self, difficulty: Difficulty) -> Result<u64, StoreError> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT count(*) FROM puzzles WHERE difficulty = ? AND used_at IS NULL",
+        )
+        .bind(difficulty.name())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count as u64)
+    }
+
+    async fn take_unused(
+        &self,
+        difficulty: Option<Difficulty>,
+    ) -> Result<Option<Puzzle>, StoreError> {
+        // SQLite runs one writer at a time, so the claim cannot race
+        let query = format!(
+            "UPDATE puzzles SET used_at = ?1 WHERE id = (\
+                 SELECT id FROM puzzles \
+                 WHERE used_at IS NULL AND (?2 IS NULL OR difficulty = ?2) \
+                 ORDER BY created_at, id LIMIT 1) \
+             RETURNING {}",
+            COLUMNS
+        );
+        Ok(sqlx::query_as::<_, Puzzle>(&query)
+            .bind(Utc::now())
+            .bind(difficulty.map(|d| d.name()))
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn random(
+        &self,
+        difficulty: Option<Difficulty>,
+        unseen_by: Option<&str>,
+    ) -> Result<Option<Puzzle>, StoreError> {
+        let query = format!(
+            "SELECT {} FROM puzzles WHERE random_key >= ?1 {} \
+             AND NOT EXISTS (SELECT 1 FROM seen_puzzles s \
+                             WHERE s.player = ?2 AND s.puzzle_id = puzzles.id) \
+             ORDER BY random_key LIMIT 1",
+            COLUMNS,
+            if difficulty.is_some() {
+                "AND difficulty = ?3"
+            } else {
+                ""
+            }
+        );
+        // Seek from a random key along the index, wrapping round to the
+        // start when nothing lies past it
+        for pivot in [rand::random::<f64>(), 0.0] {
+            let mut pick = sqlx::query_as::<_, Puzzle>(&query)
+                .bind(pivot)
+                .bind(unseen_by);
+            if let Some(difficulty) = difficulty {
+                pick = pick.bind(difficulty.name());
+            }
+            if let Some(puzzle) = pick.fetch_optional(&self.pool).await? {
+                return Ok(Some(puzzle));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn mark_seen(&self, player: &str, puzzle_id: &str) -> Result<(), StoreError> {
+        sqlx::query(
+            "INSERT INTO seen_puzzles (player, puzzle_id) VALUES (?1, ?2) \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(player)
+        .bind(puzzle_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn migrate(&self) -> Result<(), StoreError> {
+        migrations::run(&MIGRATOR, &self.pool).await
+    }
+
+    async fn migrations(&self) -> Result<Vec<Migration>, StoreError> {
+        migrations::status(&MIGRATOR, &self.pool).await
+    }
+}
+
+#[async_trait]
+impl UserStore for SqliteStore {
+    async fn create_user(&self, user: &User, password_hash: &str) -> Result<bool, StoreError> {
+        let result = sqlx::query(
+            "INSERT INTO users (id, username, password_hash, created_at) \
+             VALUES (?1, ?2, ?3, ?4) ON CONFLICT DO NOTHING",
+        )
+        .bind(&user.id)
+        .bind(&user.username)
+        .bind(password_hash)
+        .bind(user.created_at.unwrap_or_else(Utc::now))
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn user_by_name(&self, username: &str) -> Result<Option<(User, String)>, StoreError> {
+        let row: Option<(String, String, DateTime<Utc>, String)> = sqlx::query_as(
+            "SELECT id, username, created_at, password_hash FROM users WHERE username = ?1",
+        )
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|(id, username, created_at, hash)| {
+            let user = User {
+                id,
+                username,
+                created_at: Some(created_at),
+            };
+            (user, hash)
+        }))
+    }
+
+    async fn create_session(
+        &self,
+        token_hash: &str,
+        user_id: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), StoreError> {
+        sqlx::query("DELETE FROM sessions WHERE expires_at <= ?1")
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            "INSERT INTO sessions (token_hash, user_id, created_at, expires_at) \
+             VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind(token_hash)
+        .bind(user_id)
+        .bind(Utc::now())
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn session_user(&self, token_hash: &str) -> Result<Option<User>, StoreError> {
+        Ok(sqlx::query_as::<_, User>(
+            "SELECT users.id, users.username, users.created_at FROM sessions \
+             JOIN users ON users.id = sessions.user_id \
+             WHERE sessions.token_hash = ?1 AND sessions.expires_at > ?2",
+        )
+        .bind(token_hash)
+        .bind(Utc::now())
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    async fn delete_session(&self, token_hash: &str) -> Result<(), StoreError> {
+        sqlx::query("DELETE FROM sessions WHERE token_hash = ?1")
+            .bind(token_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SqliteStore;
+    use crate::store::{MigrationState, PuzzleStore, StoreError};
+    use crate::users::{User, UserStore};
+    use chrono::{Duration, Utc};
+
+    #[tokio::test]
+    async fn migrates_and_refuses_a_newer_schema() {
+        let store = SqliteStore::connect("sqlite::memory:").await.unwrap();
+        let before = store.migrations().await.unwrap();
+        assert!(before.iter().all(|m| m.state == MigrationState::Pending));
+        store.migrate().await.unwrap();
+        let after = store.migrations().await.unwrap();
+        assert_eq!(after.len(), before.len());
+        assert!(after.iter().all(|m| m.state == MigrationState::Applied));
+
+        sqlx::query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) \
+             VALUES (29990101000000, 'from the future', TRUE, x'00', 0)",
+        )
+        .execute(&store.pool)
+        .await
+        .unwrap();
+        let last = store.migrations().await.unwrap().pop().unwrap();
+        assert_eq!(last.state, MigrationState::Unknown);
+        assert!(matches!(
+            store.migrate().await,
+            Err(StoreError::SchemaAhead(versions)) if versions == vec![29990101000000]
+        ));
+    }
+
+    #[tokio::test]
+    async fn drops_expired_sessions() {
+        let store = SqliteStore::connect("sqlite::memory:").await.unwrap();
+        store.migrate().await.unwrap();
+        let ann = User::new("ann");
+        store.create_user(&ann, "hash").await.unwrap();
+        let yesterday = Utc::now() - Duration::days(1);
+        store
+            .create_session("old", &ann.id, yesterday)
+            .await
+            .unwrap();
+        let tomorrow = Utc::now() + Duration::days(1);
+        store
+            .create_session("new", &ann.id, tomorrow)
+            .await
+            .unwrap();
+        let tokens: Vec<(String,)> = sqlx::query_as("SELECT token_hash FROM sessions")
+            .fetch_all(&store.pool)
+            .await
+            .unwrap();
+        assert_eq!(tokens, vec![("new".to_string(),)]);
+    }
+}
@@ -0,0 +1,335 @@
+use crate::memory::MemoryStore;
+use crate::users::UserStore;
+use crate::{Difficulty, Puzzle};
+use async_trait::async_trait;
+use std::fmt::{Display, Formatter};
+
+/// Somewhere to keep puzzles. The backend and the generator only talk to
+/// storage through this trait, so they run the same against any of them.
+#[async_trait]
+pub trait PuzzleStore: Send + Sync {
+    /// Every stored puzzle, oldest first.
+    async fn all(&self) -> Result<Vec<Puzzle>, StoreError>;
+
+    /// Up to `limit` puzzles in id order, starting after the id `after`, for
+    /// reading the whole store a page at a time.
+    async fn page(&self, after: Option<&str>, limit: u32) -> Result<Vec<Puzzle>, StoreError>;
+
+    /// The puzzles imported from one collection, oldest first.
+    async fn by_source(&self, source: &str) -> Result<Vec<Puzzle>, StoreError>;
+
+    async fn get(&self, id: &str) -> Result<Option<Puzzle>, StoreError>;
+
+    /// Adds a puzzle, keeping its id and creation time. Returns false when a
+    /// puzzle with the same id or the same grid is already stored.
+    async fn insert(&self, puzzle: &Puzzle) -> Result<bool, StoreError>;
+
+    /// Adds puzzles like [`PuzzleStore::insert`], all or none of them at
+    /// once where the store has transactions. Returns how many were new.
+    async fn insert_many(&self, puzzles: &[Puzzle]) -> Result<u64, StoreError> {
+        let mut inserted = 0;
+        for puzzle in puzzles {
+            if self.insert(puzzle).await? {
+                inserted += 1;
+            }
+        }
+        Ok(inserted)
+    }
+
+    /// How many puzzles of `difficulty` the pool has not handed out yet.
+    async fn count_unused(&self, difficulty: Difficulty) -> Result<u64, StoreError>;
+
+    /// Marks the oldest puzzle the pool has not handed out yet as used and
+    /// returns it, picking from one difficulty when given.
+    async fn take_unused(
+        &self,
+        difficulty: Option<Difficulty>,
+    ) -> Result<Option<Puzzle>, StoreError>;
+
+    /// A stored puzzle picked at random, of `difficulty` when given, leaving
+    /// out the ones `unseen_by` has been shown.
+    async fn random(
+        &self,
+        difficulty: Option<Difficulty>,
+        unseen_by: Option<&str>,
+    ) -> Result<Option<Puzzle>, StoreError>;
+
+    /// Remembers that `player` was shown a puzzle, so random picks for them
+    /// skip it.
+    async fn mark_seen(&self, player: &str, puzzle_id: &str) -> Result<(), StoreError>;
+
+    /// Applies the migrations this build has and the store lacks. Refuses to
+    /// touch a store that has migrations this build does not know about.
+    /// Stores without a schema have nothing to do.
+    async fn migrate(&self) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    /// Every migration known to this build or applied to the store, by
+    /// version.
+    async fn migrations(&self) -> Result<Vec<Migration>, StoreError> {
+        Ok(vec![])
+    }
+}
+
+/// Puzzles and accounts together, as every store keeps both.
+pub trait Store: PuzzleStore + UserStore {}
+
+impl<T: PuzzleStore + UserStore> Store for T {}
+
+/// A schema migration and whether the store has it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    pub version: i64,
+    /// Empty for migrations this build does not have
+    pub description: String,
+    pub state: MigrationState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but this build has a different script for it
+    Modified,
+    /// Applied by a newer build
+    Unknown,
+}
+
+impl Display for MigrationState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::Modified => "modified",
+            MigrationState::Unknown => "unknown",
+        };
+        f.pad(name)
+    }
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    /// The URL names a store this build does not include, or none at all
+    Unsupported(String),
+    /// The database failed or refused the query
+    Database(Box<dyn std::error::Error + Send + Sync>),
+    /// The schema has these migrations, which this build does not
+    SchemaAhead(Vec<i64>),
+}
+
+impl Display for StoreError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoreError::Unsupported(reason) => write!(f, "{}", reason),
+            StoreError::Database(e) => write!(f, "database error: {}", e),
+            StoreError::SchemaAhead(versions) => {
+                let versions: Vec<String> = versions.iter().map(|v| v.to_string()).collect();
+                write!(
+                    f,
+                    "the database has migrations this build does not know about ({}), \
+                     run a newer build against it",
+                    versions.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+#[cfg(feature = "sqlx")]
+impl From<sqlx::Error> for StoreError {
+    fn from(e: sqlx::Error) -> Self {
+        StoreError::Database(Box::new(e))
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl From<sqlx::migrate::MigrateError> for StoreError {
+    fn from(e: sqlx::migrate::MigrateError) -> Self {
+        StoreError::Database(Box::new(e))
+    }
+}
+
+/// Opens the store a URL points at: `postgres://...`, `sqlite:path` (or
+/// `sqlite::memory:`), or `memory:` for a store that lives as long as the
+/// process.
+pub async fn connect(url: &str) -> Result<Box<dyn Store>, StoreError> {
+    let scheme = url.split(':').next().unwrap_or_default();
+    match scheme {
+        "memory" => Ok(Box::new(MemoryStore::default())),
+        #[cfg(feature = "postgres")]
+        "postgres" | "postgresql" => Ok(Box::new(crate::postgres::PgStore::connect(url).await?)),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => Ok(Box::new(crate::sqlite::SqliteStore::connect(url).await?)),
+        #[cfg(not(feature = "postgres"))]
+        "postgres" | "postgresql" => Err(StoreError::Unsupported(
+            "this build has no Postgres support, enable the postgres feature".to_string(),
+        )),
+        #[cfg(not(feature = "sqlite"))]
+        "sqlite" => Err(StoreError::Unsupported(
+            "this build has no SQLite support, enable the sqlite feature".to_string(),
+        )),
+        _ => Err(StoreError::Unsupported(format!(
+            "unknown store '{}', expected a postgres://, sqlite: or memory: URL",
+            url
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{connect, Store};
+    use crate::{Difficulty, Puzzle};
+    use std::collections::HashSet;
+    use sudoku::Sudoku;
+
+    /// An empty, migrated store of every kind that runs in memory.
+    async fn stores() -> Vec<Box<dyn Store>> {
+        let mut urls = vec!["memory:"];
+        if cfg!(feature = "sqlite") {
+            urls.push("sqlite::memory:");
+        }
+        let mut stores = vec![];
+        for url in urls {
+            let store = connect(url).await.unwrap();
+            store.migrate().await.unwrap();
+            stores.push(store);
+        }
+        stores
+    }
+
+    /// The first `count` puzzles of top95, with their source lines.
+    fn top95(count: usize) -> Vec<Puzzle> {
+        std::fs::read_to_string("../sudokus/top95.txt")
+            .unwrap()
+            .lines()
+            .take(count)
+            .enumerate()
+            .map(|(idx, l)| Puzzle {
+                source: Some("top95".to_string()),
+                source_line: Some(idx as i32 + 1),
+                ..Puzzle::from_sudoku(&Sudoku::from_str_line(l).unwrap()).unwrap()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn gets_and_filters_by_source() {
+        for store in stores().await {
+            let puzzles = top95(2);
+            for puzzle in &puzzles {
+                assert!(store.insert(puzzle).await.unwrap());
+            }
+            let same_grid = Puzzle::from_sudoku(&puzzles[0].sudoku().unwrap()).unwrap();
+            assert!(!store.insert(&same_grid).await.unwrap());
+            let stored = store.get(&puzzles[1].id).await.unwrap().unwrap();
+            assert_eq!(stored.puzzle, puzzles[1].puzzle);
+            assert_eq!(stored.source_line, Some(2));
+            assert!(stored.created_at.is_some());
+            assert_eq!(store.get("missing").await.unwrap(), None);
+            let ids: Vec<String> = store
+                .all()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|p| p.id)
+                .collect();
+            assert_eq!(ids.len(), 2);
+            assert!(ids.contains(&puzzles[0].id));
+            assert_eq!(store.by_source("top95").await.unwrap().len(), 2);
+            assert_eq!(store.by_source("sudoku17").await.unwrap(), vec![]);
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_through_every_puzzle() {
+        for store in stores().await {
+            let puzzles = top95(10);
+            store.insert_many(&puzzles).await.unwrap();
+            let mut ids = vec![];
+            let mut after: Option<String> = None;
+            loop {
+                let page = store.page(after.as_deref(), 4).await.unwrap();
+                assert!(page.len() <= 4);
+                match page.last() {
+                    Some(last) => after = Some(last.id.clone()),
+                    None => break,
+                }
+                ids.extend(page.into_iter().map(|p| p.id));
+            }
+            let mut expected: Vec<String> = puzzles.into_iter().map(|p| p.id).collect();
+            expected.sort();
+            assert_eq!(ids, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn insert_many_skips_duplicates() {
+        for store in stores().await {
+            let mut puzzles = top95(22);
+            for puzzle in &puzzles[20..] {
+                assert!(store.insert(puzzle).await.unwrap());
+            }
+            puzzles.push(Puzzle::from_sudoku(&puzzles[0].sudoku().unwrap()).unwrap());
+            assert_eq!(store.insert_many(&puzzles).await.unwrap(), 20);
+            assert_eq!(store.all().await.unwrap().len(), 22);
+        }
+    }
+
+    #[tokio::test]
+    async fn takes_and_counts_unused() {
+        for store in stores().await {
+            assert!(store.insert(&top95(1)[0]).await.unwrap());
+            assert_eq!(store.count_unused(Difficulty::Easy).await.unwrap(), 0);
+            let mut graded = Puzzle {
+                difficulty: Some(Difficulty::Easy.to_string()),
+                ..Puzzle::from_sudoku(&Sudoku::generate_unique()).unwrap()
+            };
+            assert!(store.insert(&graded).await.unwrap());
+            assert_eq!(store.count_unused(Difficulty::Easy).await.unwrap(), 1);
+            let taken = store.take_unused(Some(Difficulty::Easy)).await.unwrap();
+            graded.created_at = taken.as_ref().and_then(|p| p.created_at);
+            assert_eq!(taken.as_ref(), Some(&graded));
+            assert_eq!(store.count_unused(Difficulty::Easy).await.unwrap(), 0);
+            assert_eq!(
+                store.take_unused(Some(Difficulty::Easy)).await.unwrap(),
+                None
+            );
+            assert_eq!(store.count_unused(Difficulty::Hard).await.unwrap(), 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn random_skips_seen_puzzles() {
+        for store in stores().await {
+            let puzzles = top95(10);
+            store.insert_many(&puzzles).await.unwrap();
+            let graded = Puzzle {
+                difficulty: Some(Difficulty::Easy.to_string()),
+                ..Puzzle::from_sudoku(&Sudoku::generate_unique()).unwrap()
+            };
+            assert!(store.insert(&graded).await.unwrap());
+
+            let easy = store.random(Some(Difficulty::Easy), None).await.unwrap();
+            assert_eq!(easy.map(|p| p.id), Some(graded.id.clone()));
+            store.mark_seen("ann", &graded.id).await.unwrap();
+            store.mark_seen("ann", &graded.id).await.unwrap();
+            let unseen = store.random(Some(Difficulty::Easy), Some("ann")).await;
+            assert_eq!(unseen.unwrap(), None);
+
+            let mut seen = HashSet::new();
+            while let Some(puzzle) = store.random(None, Some("bob")).await.unwrap() {
+                assert!(seen.insert(puzzle.id.clone()));
+                store.mark_seen("bob", &puzzle.id).await.unwrap();
+            }
+            assert_eq!(seen.len(), puzzles.len() + 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_urls() {
+        assert!(connect("mysql://localhost/sudoku").await.is_err());
+    }
+}
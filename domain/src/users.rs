@@ -0,0 +1,99 @@
+use crate::store::StoreError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A player account. Its password hash only ever leaves the store for
+/// checking a login.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct User {
+    pub id: String,
+    pub username: String,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl User {
+    /// A new account with a fresh id, created now.
+    pub fn new(username: &str) -> Self {
+        User {
+            id: ulid::Ulid::new().to_string(),
+            username: username.to_string(),
+            created_at: Some(Utc::now()),
+        }
+    }
+}
+
+/// Accounts and their login sessions. Sessions are looked up by a hash of
+/// their token, never by the token itself.
+#[async_trait]
+pub trait UserStore: Send + Sync {
+    /// Adds an account. Returns false when the username is taken.
+    async fn create_user(&self, user: &User, password_hash: &str) -> Result<bool, StoreError>;
+
+    /// The account called `username`, with its password hash.
+    async fn user_by_name(&self, username: &str) -> Result<Option<(User, String)>, StoreError>;
+
+    /// Starts a session, dropping every one that has expired, so sessions
+    /// do not pile up.
+    async fn create_session(
+        &self,
+        token_hash: &str,
+        user_id: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), StoreError>;
+
+    /// The user a session belongs to, unless it has expired.
+    async fn session_user(&self, token_hash: &str) -> Result<Option<User>, StoreError>;
+
+    async fn delete_session(&self, token_hash: &str) -> Result<(), StoreError>;
+}
+
+#[cfg(test)]
+mod test {
+    use super::User;
+    use crate::store::{connect, Store};
+    use chrono::{Duration, Utc};
+
+    async fn exercise(store: &dyn Store) {
+        let ann = User::new("ann");
+        assert!(store.create_user(&ann, "hash").await.unwrap());
+        assert!(!store.create_user(&User::new("ann"), "other").await.unwrap());
+        let (found, hash) = store.user_by_name("ann").await.unwrap().unwrap();
+        assert_eq!(
+            (found.id.as_str(), hash.as_str()),
+            (ann.id.as_str(), "hash")
+        );
+        assert!(store.user_by_name("bob").await.unwrap().is_none());
+
+        let tomorrow = Utc::now() + Duration::days(1);
+        store
+            .create_session("live", &ann.id, tomorrow)
+            .await
+            .unwrap();
+        let yesterday = Utc::now() - Duration::days(1);
+        store
+            .create_session("old", &ann.id, yesterday)
+            .await
+            .unwrap();
+        let user = store.session_user("live").await.unwrap().unwrap();
+        assert_eq!(user.username, "ann");
+        assert!(store.session_user("old").await.unwrap().is_none());
+        store.delete_session("live").await.unwrap();
+        assert!(store.session_user("live").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn memory_users() {
+        exercise(connect("memory:").await.unwrap().as_ref()).await;
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn sqlite_users() {
+        let store = connect("sqlite::memory:").await.unwrap();
+        store.migrate().await.unwrap();
+        exercise(store.as_ref()).await;
+    }
+}
@@ -0,0 +1,190 @@
+use crate::import::{ImportStats, Importer};
+use clap::ArgEnum;
+use domain::store::{PuzzleStore, StoreError};
+use domain::Puzzle;
+use std::io;
+use std::io::{BufRead, Write};
+
+/// File formats used to move the puzzles table between environments.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExchangeFormat {
+    /// One JSON object per line
+    Ndjson,
+    /// Comma separated with a header row
+    Csv,
+}
+
+/// Puzzles read from the store per query while exporting.
+const PAGE_SIZE: u32 = 1000;
+
+/// Writes rows in either exchange format.
+enum RowWriter<W: Write> {
+    Ndjson(W),
+    Csv(Box<csv::Writer<W>>),
+}
+
+impl<W: Write> RowWriter<W> {
+    fn new(format: ExchangeFormat, out: W) -> Self {
+        match format {
+            ExchangeFormat::Ndjson => RowWriter::Ndjson(out),
+            ExchangeFormat::Csv => RowWriter::Csv(Box::new(csv::Writer::from_writer(out))),
+        }
+    }
+
+    fn write(&mut self, row: &Puzzle) -> io::Result<()> {
+        match self {
+            RowWriter::Ndjson(out) => {
+                serde_json::to_writer(&mut *out, row)?;
+                writeln!(out)
+            }
+            RowWriter::Csv(writer) => writer.serialize(row).map_err(io::Error::from),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            RowWriter::Ndjson(out) => out.flush(),
+            RowWriter::Csv(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Writes every stored puzzle to `out`, reading the store a page at a time
+/// so the table never has to fit in memory. Returns how many were written.
+pub async fn export<W: Write>(
+    store: &dyn PuzzleStore,
+    format: ExchangeFormat,
+    out: W,
+) -> io::Result<usize> {
+    let mut writer = RowWriter::new(format, out);
+    let mut written = 0;
+    let mut after: Option<String> = None;
+    loop {
+        let page = store
+            .page(after.as_deref(), PAGE_SIZE)
+            .await
+            .map_err(io::Error::other)?;
+        for row in &page {
+            writer.write(row)?;
+        }
+        written += page.len();
+        match page.last() {
+            Some(last) if page.len() == PAGE_SIZE as usize => after = Some(last.id.clone()),
+            _ => break,
+        }
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
+/// The rows of `input` as they are read, each parsed or with the reason it
+/// could not be.
+fn read_rows<'a, R: BufRead + 'a>(
+    format: ExchangeFormat,
+    input: R,
+) -> Box<dyn Iterator<Item = Result<Puzzle, String>> + 'a> {
+    match format {
+        ExchangeFormat::Ndjson => Box::new(
+            input
+                .lines()
+                .enumerate()
+                .filter(|(_, l)| l.as_ref().map(|l| !l.trim().is_empty()).unwrap_or(true))
+                .map(|(idx, line)| {
+                    line.map_err(|e| e.to_string())
+                        .and_then(|l| serde_json::from_str(&l).map_err(|e| e.to_string()))
+                        .map_err(|e| format!("line {}: {}", idx + 1, e))
+                }),
+        ),
+        ExchangeFormat::Csv => Box::new(
+            csv::Reader::from_reader(input)
+                .into_deserialize()
+                .map(|row| row.map_err(|e| e.to_string())),
+        ),
+    }
+}
+
+/// Inserts rows written by [`export`], keeping their ids and creation times,
+/// in batches as they are read. Rows whose id or puzzle already exist are
+/// skipped.
+pub async fn import<R: BufRead>(
+    store: &dyn PuzzleStore,
+    format: ExchangeFormat,
+    input: R,
+) -> Result<ImportStats, StoreError> {
+    let mut importer = Importer::new(store);
+    for row in read_rows(format, input) {
+        match row.and_then(|p| p.validate().map_err(|e| e.to_string()).map(|_| p)) {
+            Ok(p) => importer.add(p).await?,
+            Err(e) => importer.invalid(e),
+        }
+    }
+    importer.finish().await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{export, import, ExchangeFormat};
+    use domain::memory::MemoryStore;
+    use domain::store::PuzzleStore;
+    use domain::Puzzle;
+    use sudoku::Sudoku;
+
+    /// A store holding the first `count` puzzles of top95.
+    async fn store(count: usize) -> MemoryStore {
+        let store = MemoryStore::default();
+        let puzzles: Vec<Puzzle> = std::fs::read_to_string("../sudokus/top95.txt")
+            .unwrap()
+            .lines()
+            .take(count)
+            .map(|l| Puzzle::from_sudoku(&Sudoku::from_str_line(l).unwrap()).unwrap())
+            .collect();
+        store.insert_many(&puzzles).await.unwrap();
+        store
+    }
+
+    async fn exported(store: &MemoryStore, format: ExchangeFormat) -> Vec<u8> {
+        let mut out = vec![];
+        export(store, format, &mut out).await.unwrap();
+        out
+    }
+
+    #[actix_web::test]
+    async fn round_trips_both_formats() {
+        let source = store(20).await;
+        for format in [ExchangeFormat::Ndjson, ExchangeFormat::Csv] {
+            let rows = exported(&source, format).await;
+            let target = MemoryStore::default();
+            let stats = import(&target, format, rows.as_slice()).await.unwrap();
+            assert_eq!((stats.inserted, stats.skipped, stats.invalid), (20, 0, 0));
+            assert_eq!(target.all().await.unwrap(), source.all().await.unwrap());
+        }
+    }
+
+    #[actix_web::test]
+    async fn skips_rows_already_stored() {
+        let source = store(20).await;
+        let target = store(5).await;
+        for format in [ExchangeFormat::Ndjson, ExchangeFormat::Csv] {
+            let rows = exported(&source, format).await;
+            import(&target, format, rows.as_slice()).await.unwrap();
+        }
+        assert_eq!(target.all().await.unwrap().len(), 20);
+        let rows = exported(&source, ExchangeFormat::Ndjson).await;
+        let stats = import(&target, ExchangeFormat::Ndjson, rows.as_slice())
+            .await
+            .unwrap();
+        assert_eq!((stats.inserted, stats.skipped, stats.invalid), (0, 20, 0));
+    }
+
+    #[actix_web::test]
+    async fn counts_invalid_rows() {
+        let rows = exported(&store(3).await, ExchangeFormat::Ndjson).await;
+        let mut text = String::from_utf8(rows).unwrap();
+        text.push_str("not json\n\n{\"id\": \"no puzzle\"}\n");
+        let target = MemoryStore::default();
+        let stats = import(&target, ExchangeFormat::Ndjson, text.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!((stats.inserted, stats.skipped, stats.invalid), (3, 0, 2));
+    }
+}
@@ -0,0 +1,85 @@
+use domain::store::{PuzzleStore, StoreError};
+use domain::Puzzle;
+use std::fmt::{Display, Formatter};
+use std::time::Instant;
+
+/// Puzzles written to the store per transaction.
+const BATCH_SIZE: usize = 1000;
+
+/// What happened to the rows of an import.
+#[derive(Debug, Default)]
+pub struct ImportStats {
+    pub inserted: u64,
+    pub skipped: u64,
+    pub invalid: u64,
+}
+
+impl Display for ImportStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} inserted, {} skipped as already present, {} invalid",
+            self.inserted, self.skipped, self.invalid
+        )
+    }
+}
+
+/// Collects puzzles and writes them to the store in batches, reporting
+/// progress on stderr after each one.
+pub struct Importer<'a> {
+    store: &'a dyn PuzzleStore,
+    batch: Vec<Puzzle>,
+    stats: ImportStats,
+    started: Instant,
+}
+
+impl<'a> Importer<'a> {
+    pub fn new(store: &'a dyn PuzzleStore) -> Self {
+        Importer {
+            store,
+            batch: Vec::with_capacity(BATCH_SIZE),
+            stats: ImportStats::default(),
+            started: Instant::now(),
+        }
+    }
+
+    pub async fn add(&mut self, puzzle: Puzzle) -> Result<(), StoreError> {
+        self.batch.push(puzzle);
+        if self.batch.len() >= BATCH_SIZE {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Counts a row that could not be imported, saying why on stderr.
+    pub fn invalid(&mut self, reason: impl Display) {
+        eprintln!("Skipping invalid row: {}", reason);
+        self.stats.invalid += 1;
+    }
+
+    /// Writes what is left and returns the totals.
+    pub async fn finish(mut self) -> Result<ImportStats, StoreError> {
+        self.flush().await?;
+        Ok(self.stats)
+    }
+
+    async fn flush(&mut self) -> Result<(), StoreError> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let inserted = self.store.insert_many(&self.batch).await?;
+        self.stats.inserted += inserted;
+        self.stats.skipped += self.batch.len() as u64 - inserted;
+        self.batch.clear();
+        let done = self.stats.inserted + self.stats.skipped + self.stats.invalid;
+        let elapsed = self.started.elapsed().as_secs_f64();
+        eprintln!(
+            "{} rows in {:.1}s, {:.0} per second: {}",
+            done,
+            elapsed,
+            done as f64 / elapsed,
+            self.stats
+        );
+        Ok(())
+    }
+}
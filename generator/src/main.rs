@@ -1,44 +1,20 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use domain::store::PuzzleStore;
+use domain::Puzzle;
 use dotenv::dotenv;
-use serde::{Deserialize, Serialize};
-use sqlx::postgres::PgPoolOptions;
-use sqlx::{Pool, Postgres};
-use std::fmt::{Display, Formatter};
+use exchange::ExchangeFormat;
+use import::Importer;
+use solver::formats::{Format, Puzzles};
+use solver::steps::grade;
 use std::fs::File;
-use std::io::BufRead;
+use std::path::Path;
+use std::thread;
+use std::time::Instant;
 use std::{env, io};
-use sudoku::Sudoku;
 
-#[derive(Serialize, Deserialize, Debug, sqlx::FromRow)]
-struct Puzzle {
-    id: String,
-    puzzle: String,
-    solution: String,
-    num_clues: i16,
-}
-
-impl Display for Puzzle {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Puzzle(id: {}, puzzle: {}, solution: {}, num_clues: {})",
-            &self.id, &self.puzzle, &self.solution, &self.num_clues
-        )
-    }
-}
-async fn insert_puzzle(puzzle: Puzzle, pool: &Pool<Postgres>) {
-    println!("Inserting {}", puzzle);
-    sqlx::query!(
-        "INSERT INTO puzzles (id, puzzle, solution, num_clues) VALUES ($1, $2, $3, $4)",
-        puzzle.id,
-        puzzle.puzzle,
-        puzzle.solution,
-        puzzle.num_clues
-    )
-    .execute(pool)
-    .await
-    .expect("Managed to insert");
-}
+mod exchange;
+mod import;
+mod pipeline;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -46,86 +22,163 @@ struct Args {
     #[clap(short, long)]
     count: Option<u32>,
 
+    /// Threads generating puzzles for --count, one per CPU when not given
+    #[clap(short, long)]
+    workers: Option<usize>,
+
     #[clap(short, long)]
     file: Option<String>,
+
+    /// Format of the puzzles in --file, detected when not given
+    #[clap(long, arg_enum)]
+    format: Option<Format>,
+
+    /// Collection the puzzles in --file belong to, the file name without
+    /// its extension when not given
+    #[clap(long)]
+    source: Option<String>,
+
+    /// Author of the puzzles in --file
+    #[clap(long)]
+    author: Option<String>,
+
+    /// Where the puzzles in --file were published
+    #[clap(long)]
+    source_url: Option<String>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
-fn puzzle_from_sudoku(puzzle: String, solution: String) -> Puzzle {
-    let num_clues = puzzle.clone().chars().filter(|f| *f != '.').count() as i16;
-    Puzzle {
-        id: ulid::Ulid::new().to_string(),
-        puzzle,
-        solution,
-        num_clues,
-    }
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Dump the puzzles table
+    Export {
+        #[clap(long, arg_enum, default_value = "ndjson")]
+        format: ExchangeFormat,
+
+        /// File to write to instead of stdout
+        #[clap(short, long)]
+        output: Option<String>,
+    },
+    /// Load puzzles written by export, skipping ones that already exist
+    Import {
+        #[clap(long, arg_enum, default_value = "ndjson")]
+        format: ExchangeFormat,
+
+        /// File to read from instead of stdin
+        #[clap(short, long)]
+        input: Option<String>,
+    },
 }
 
-fn generate_puzzle() -> Puzzle {
-    let puzzle = Sudoku::generate_unique();
-    puzzle_from_sudoku(
-        puzzle.to_string(),
-        puzzle
-            .solve_unique()
-            .expect("Just generated it")
-            .to_string(),
-    )
+async fn connect() -> Box<dyn PuzzleStore> {
+    let url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let store = domain::store::connect(&url)
+        .await
+        .expect("Failed to open the puzzle store");
+    store
+        .migrate()
+        .await
+        .expect("Failed to bring the database schema up to date");
+    store
+}
+
+async fn run_command(command: Command) -> io::Result<()> {
+    let store = connect().await;
+    match command {
+        Command::Export { format, output } => {
+            let exported = match output {
+                Some(path) => exchange::export(store.as_ref(), format, File::create(path)?).await?,
+                None => exchange::export(store.as_ref(), format, io::stdout().lock()).await?,
+            };
+            eprintln!("Exported {} puzzles", exported);
+        }
+        Command::Import { format, input } => {
+            let stats = match input {
+                Some(path) => {
+                    exchange::import(
+                        store.as_ref(),
+                        format,
+                        io::BufReader::new(File::open(path)?),
+                    )
+                    .await
+                }
+                None => exchange::import(store.as_ref(), format, io::stdin().lock()).await,
+            }
+            .map_err(io::Error::other)?;
+            println!("Import finished: {}", stats);
+        }
+    }
+    Ok(())
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
     let args = Args::parse();
+    if let Some(command) = args.command {
+        return run_command(command).await;
+    }
     match args.file {
         Some(f) => {
-            let file = File::open(f).expect("File must exist");
-            let pool = PgPoolOptions::new()
-                .max_connections(5)
-                .connect(
-                    env::var("DATABASE_URL")
-                        .expect("DATABASE_URL must be set")
-                        .as_str(),
-                )
-                .await
-                .expect("Failed to connect to POSTGRES");
-
-            for line in io::BufReader::new(file).lines() {
-                match line {
-                    Ok(l) => match Sudoku::from_str_line(&l) {
-                        Ok(s) => {
-                            let p = puzzle_from_sudoku(
-                                s.to_string(),
-                                s.solve_unique()
-                                    .expect("Generated from an already solved puzzle")
-                                    .to_string(),
-                            );
-                            insert_puzzle(p, &pool).await;
-                        }
-                        _ => {}
-                    },
-                    _ => {
-                        println!("Could not find a puzzle");
+            let source = args.source.clone().unwrap_or_else(|| {
+                Path::new(&f)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "custom".to_string())
+            });
+            let file = File::open(&f).expect("File must exist");
+            let store = connect().await;
+            let mut importer = Importer::new(store.as_ref());
+            for entry in Puzzles::new(io::BufReader::new(file), args.format) {
+                let entry = entry?;
+                let puzzle = match entry.puzzle {
+                    Ok(s) => {
+                        Puzzle::from_sudoku(&s).map_err(|e| format!("line {}: {}", entry.line, e))
                     }
+                    Err(e) => Err(e.at_line(entry.line).to_string()),
                 };
+                match puzzle {
+                    Ok(p) => {
+                        let p = Puzzle {
+                            difficulty: p.sudoku().ok().map(|s| grade(s).to_string()),
+                            source: Some(source.clone()),
+                            source_line: i32::try_from(entry.line).ok(),
+                            author: args.author.clone(),
+                            source_url: args.source_url.clone(),
+                            ..p
+                        };
+                        importer.add(p).await.map_err(io::Error::other)?
+                    }
+                    Err(e) => importer.invalid(e),
+                }
             }
+            let stats = importer.finish().await.map_err(io::Error::other)?;
+            println!("Import finished: {}", stats);
         }
         None => {}
     }
     match args.count {
         Some(count) => {
             if count > 0 {
-                let pool = PgPoolOptions::new()
-                    .max_connections(5)
-                    .connect(
-                        env::var("DATABASE_URL")
-                            .expect("DATABASE_URL must be set")
-                            .as_str(),
-                    )
+                let workers = args
+                    .workers
+                    .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+                let store = connect().await;
+                let started = Instant::now();
+                let stats = pipeline::generate(store.as_ref(), count, workers.max(1))
                     .await
-                    .expect("Failed to connect to POSTGRES");
-                for _ in 0..count {
-                    let puzzle = generate_puzzle();
-                    insert_puzzle(puzzle, &pool).await;
-                }
+                    .map_err(io::Error::other)?;
+                let elapsed = started.elapsed().as_secs_f64();
+                println!(
+                    "Generated {} puzzles on {} workers in {:.1}s, {:.0} per second: {}",
+                    count,
+                    workers,
+                    elapsed,
+                    f64::from(count) / elapsed,
+                    stats
+                );
             }
         }
         _ => println!("Got told to generate 0 puzzles"),
@@ -0,0 +1,39 @@
+use crate::import::{ImportStats, Importer};
+use domain::store::{PuzzleStore, StoreError};
+use domain::Puzzle;
+use solver::solver::generate_puzzle;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tokio::sync::mpsc;
+
+/// Generated puzzles waiting for the writer before workers have to wait.
+const QUEUE_SIZE: usize = 4096;
+
+/// Generates and grades `count` puzzles on `workers` threads while the
+/// calling task writes them to the store in batches as they arrive.
+pub async fn generate(
+    store: &dyn PuzzleStore,
+    count: u32,
+    workers: usize,
+) -> Result<ImportStats, StoreError> {
+    let (tx, mut rx) = mpsc::channel::<Puzzle>(QUEUE_SIZE);
+    let next = Arc::new(AtomicU32::new(0));
+    for _ in 0..workers {
+        let (tx, next) = (tx.clone(), next.clone());
+        thread::spawn(move || {
+            while next.fetch_add(1, Ordering::Relaxed) < count {
+                // The writer is gone after a store error, nothing left to do
+                if tx.blocking_send(generate_puzzle()).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(tx);
+    let mut importer = Importer::new(store);
+    while let Some(puzzle) = rx.recv().await {
+        importer.add(puzzle).await?;
+    }
+    importer.finish().await
+}
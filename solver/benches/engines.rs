@@ -0,0 +1,60 @@
+//! Throughput of every built-in engine over the bundled corpora. Puzzles are
+//! parsed up front, so only solving is measured, unlike `solver --file`.
+//!
+//! The SAT engine is left out: it needs an external binary and spends most of
+//! its time starting processes.
+
+use clap::ArgEnum;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use solver::engine::Engine;
+use std::fs;
+use sudoku::Sudoku;
+
+/// Corpus name, path relative to the solver crate and how many puzzles to take
+const CORPORA: &[(&str, &str, usize)] = &[
+    ("100puzzles", "../100puzzles", 100),
+    ("top95", "../sudokus/top95.txt", 95),
+    ("easy", "../sudokus/easy_sudokus.txt", 50),
+    ("medium", "../sudokus/medium_sudokus.txt", 10),
+    ("hard", "../sudokus/hard_sudokus.txt", 20),
+    ("sudoku17", "../sudokus/sudoku17", 1000),
+];
+
+/// The first `count` valid puzzles of the file at `path`.
+fn load(path: &str, count: usize) -> Vec<Sudoku> {
+    fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("Cannot read {}: {}", path, e))
+        .lines()
+        .filter_map(|line| Sudoku::from_str_line(line).ok())
+        .take(count)
+        .collect()
+}
+
+fn engines(c: &mut Criterion) {
+    for &(name, path, count) in CORPORA {
+        let puzzles = load(path, count);
+        let mut group = c.benchmark_group(name);
+        group.throughput(Throughput::Elements(puzzles.len() as u64));
+        for &engine in Engine::value_variants() {
+            if engine == Engine::Sat {
+                continue;
+            }
+            group.bench_with_input(
+                BenchmarkId::from_parameter(engine),
+                &puzzles,
+                |b, puzzles| {
+                    b.iter(|| {
+                        puzzles
+                            .iter()
+                            .filter_map(|&p| engine.solve(p).expect("Built-in engines always run"))
+                            .count()
+                    })
+                },
+            );
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, engines);
+criterion_main!(benches);
@@ -0,0 +1,256 @@
+use sudoku::Sudoku;
+
+/// Sparse 0/1 matrix for Knuth's Algorithm X, stored as dancing links. Node
+/// 0 is the root, nodes `1..=columns` are the column headers and every other
+/// node is a 1 in some row. Primary columns must be covered exactly once,
+/// secondary columns at most once.
+pub struct Matrix {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    column: Vec<usize>,
+    row: Vec<usize>,
+    size: Vec<usize>,
+    rows: usize,
+}
+
+impl Matrix {
+    pub fn new(primary: usize, secondary: usize) -> Self {
+        let headers = primary + secondary + 1;
+        let mut matrix = Matrix {
+            left: Vec::with_capacity(headers),
+            right: Vec::with_capacity(headers),
+            up: (0..headers).collect(),
+            down: (0..headers).collect(),
+            column: (0..headers).collect(),
+            row: vec![usize::MAX; headers],
+            size: vec![0; headers],
+            rows: 0,
+        };
+        for node in 0..headers {
+            if node <= primary {
+                matrix.left.push(if node == 0 { primary } else { node - 1 });
+                matrix
+                    .right
+                    .push(if node == primary { 0 } else { node + 1 });
+            } else {
+                matrix.left.push(node);
+                matrix.right.push(node);
+            }
+        }
+        matrix
+    }
+
+    /// Adds a row with a 1 in each of the given (0-based) columns, returning
+    /// its index.
+    pub fn add_row(&mut self, columns: &[usize]) -> usize {
+        let row = self.rows;
+        self.rows += 1;
+        let first = self.left.len();
+        for (idx, &col) in columns.iter().enumerate() {
+            let header = col + 1;
+            let node = first + idx;
+            self.left.push(if idx == 0 {
+                first + columns.len() - 1
+            } else {
+                node - 1
+            });
+            self.right.push(if idx + 1 == columns.len() {
+                first
+            } else {
+                node + 1
+            });
+            self.up.push(self.up[header]);
+            self.down.push(header);
+            self.column.push(header);
+            self.row.push(row);
+            let last = self.up[header];
+            self.down[last] = node;
+            self.up[header] = node;
+            self.size[header] += 1;
+        }
+        row
+    }
+
+    fn cover(&mut self, header: usize) {
+        let (l, r) = (self.left[header], self.right[header]);
+        self.right[l] = r;
+        self.left[r] = l;
+        let mut i = self.down[header];
+        while i != header {
+            let mut j = self.right[i];
+            while j != i {
+                let (u, d) = (self.up[j], self.down[j]);
+                self.down[u] = d;
+                self.up[d] = u;
+                self.size[self.column[j]] -= 1;
+                j = self.right[j];
+            }
+            i = self.down[i];
+        }
+    }
+
+    fn uncover(&mut self, header: usize) {
+        let mut i = self.up[header];
+        while i != header {
+            let mut j = self.left[i];
+            while j != i {
+                self.size[self.column[j]] += 1;
+                let (u, d) = (self.up[j], self.down[j]);
+                self.down[u] = j;
+                self.up[d] = j;
+                j = self.left[j];
+            }
+            i = self.up[i];
+        }
+        let (l, r) = (self.left[header], self.right[header]);
+        self.right[l] = header;
+        self.left[r] = header;
+    }
+
+    /// Finds up to `limit` exact covers, each given as the indices of the
+    /// rows it is made of.
+    pub fn solve(&mut self, limit: usize) -> Vec<Vec<usize>> {
+        let mut solutions = vec![];
+        let mut partial = vec![];
+        self.search(&mut partial, &mut solutions, limit);
+        solutions
+    }
+
+    fn search(&mut self, partial: &mut Vec<usize>, solutions: &mut Vec<Vec<usize>>, limit: usize) {
+        if solutions.len() >= limit {
+            return;
+        }
+        if self.right[0] == 0 {
+            solutions.push(partial.iter().map(|&node| self.row[node]).collect());
+            return;
+        }
+        let mut header = self.right[0];
+        let mut best = header;
+        while header != 0 {
+            if self.size[header] < self.size[best] {
+                best = header;
+            }
+            header = self.right[header];
+        }
+        if self.size[best] == 0 {
+            return;
+        }
+        self.cover(best);
+        let mut i = self.down[best];
+        while i != best && solutions.len() < limit {
+            partial.push(i);
+            let mut j = self.right[i];
+            while j != i {
+                self.cover(self.column[j]);
+                j = self.right[j];
+            }
+            self.search(partial, solutions, limit);
+            let mut j = self.left[i];
+            while j != i {
+                self.uncover(self.column[j]);
+                j = self.left[j];
+            }
+            partial.pop();
+            i = self.down[i];
+        }
+        self.uncover(best);
+    }
+}
+
+/// Finds up to `limit` solutions of `sudoku`. Every entry of `extra_houses`
+/// is a set of 9 cells that must also hold each digit once, which covers
+/// variants such as the diagonals of X-sudoku.
+pub fn solutions(sudoku: &Sudoku, extra_houses: &[[usize; 9]], limit: usize) -> Vec<Sudoku> {
+    let mut matrix = Matrix::new(324 + 9 * extra_houses.len(), 0);
+    let mut candidates = vec![];
+    for (cell, &given) in sudoku.to_bytes().iter().enumerate() {
+        let (row, col) = (cell / 9, cell % 9);
+        let block = row / 3 * 3 + col / 3;
+        for digit in 1..=9u8 {
+            if given != 0 && given != digit {
+                continue;
+            }
+            let d = digit as usize - 1;
+            let mut columns = vec![
+                cell,
+                81 + row * 9 + d,
+                162 + col * 9 + d,
+                243 + block * 9 + d,
+            ];
+            for (idx, house) in extra_houses.iter().enumerate() {
+                if house.contains(&cell) {
+                    columns.push(324 + idx * 9 + d);
+                }
+            }
+            matrix.add_row(&columns);
+            candidates.push((cell, digit));
+        }
+    }
+    matrix
+        .solve(limit)
+        .into_iter()
+        .map(|rows| {
+            let mut bytes = [0; 81];
+            for row in rows {
+                let (cell, digit) = candidates[row];
+                bytes[cell] = digit;
+            }
+            Sudoku::from_bytes(bytes).expect("Every cell of an exact cover holds a digit")
+        })
+        .collect()
+}
+
+/// Number of solutions of `sudoku`, counting no further than `limit`.
+pub fn count_solutions(sudoku: &Sudoku, limit: usize) -> usize {
+    solutions(sudoku, &[], limit).len()
+}
+
+/// The solution of `sudoku` when it is unique.
+pub fn solve_unique(sudoku: &Sudoku) -> Option<Sudoku> {
+    match solutions(sudoku, &[], 2).as_slice() {
+        [solution] => Some(*solution),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{count_solutions, solutions, solve_unique};
+    use std::fs;
+    use sudoku::Sudoku;
+
+    #[test]
+    fn agrees_with_the_sudoku_crate_on_top_95() {
+        let puzzles = fs::read_to_string("../sudokus/top95.txt").expect("File needs to be present");
+        for line in puzzles.lines() {
+            let sudoku = Sudoku::from_str_line(line).expect("top95 puzzles are valid");
+            assert_eq!(solve_unique(&sudoku), sudoku.solve_unique());
+        }
+    }
+
+    #[test]
+    fn counts_solutions_up_to_the_limit() {
+        let empty = Sudoku::from_bytes([0; 81]).unwrap();
+        assert_eq!(count_solutions(&empty, 5), 5);
+        assert_eq!(solve_unique(&empty), None);
+    }
+
+    #[test]
+    fn extra_houses_constrain_the_solution() {
+        let diagonals = [
+            std::array::from_fn(|i| i * 10),
+            std::array::from_fn(|i| (i + 1) * 8),
+        ];
+        let empty = Sudoku::from_bytes([0; 81]).unwrap();
+        for solution in solutions(&empty, &diagonals, 3) {
+            let cells = solution.to_bytes();
+            for diagonal in &diagonals {
+                let mut digits: Vec<u8> = diagonal.iter().map(|&c| cells[c]).collect();
+                digits.sort_unstable();
+                assert_eq!(digits, (1..=9).collect::<Vec<u8>>());
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::bitboard;
+use crate::dlx;
+use crate::error::SolverError;
+use crate::sat;
+use crate::solver::{grid_from_sudoku, grid_to_sudoku, solve_grid};
+use clap::ArgEnum;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use sudoku::Sudoku;
+
+/// The solving back-ends the CLI can time against each other.
+#[derive(ArgEnum, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Engine {
+    /// `sudoku` crate, proving the solution is unique
+    #[default]
+    Sudoku,
+    /// `sudoku` crate, stopping at the first solution found, even when
+    /// there are more
+    SudokuFirst,
+    /// Built-in constraint propagation with backtracking
+    Native,
+    /// Built-in exact cover search with Dancing Links
+    Dlx,
+    /// External SAT solver, see `--sat-binary`
+    Sat,
+    /// Built-in band-oriented bitboards, tuned for bulk runs
+    Bitboard,
+}
+
+impl Engine {
+    /// The unique solution of `sudoku`, or none when it has no solution or
+    /// several. [`Engine::SudokuFirst`] is the exception: it returns the
+    /// first solution it finds without checking for others. Fails only when
+    /// the engine itself cannot run, as the SAT engine does without its
+    /// binary.
+    pub fn solve(self, sudoku: Sudoku) -> Result<Option<Sudoku>, SolverError> {
+        Ok(match self {
+            Engine::Sudoku => sudoku.solve_unique(),
+            Engine::SudokuFirst => sudoku.solve_one(),
+            Engine::Native => solve_grid(grid_from_sudoku(&sudoku)).map(|g| grid_to_sudoku(&g)),
+            Engine::Dlx => dlx::solve_unique(&sudoku),
+            Engine::Bitboard => bitboard::solve_unique(&sudoku),
+            Engine::Sat => sat::solve_unique(&sudoku)?,
+        })
+    }
+}
+
+impl Display for Engine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = self
+            .to_possible_value()
+            .map(|v| v.get_name())
+            .unwrap_or("unknown");
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Engine;
+    use clap::ArgEnum;
+    use sudoku::Sudoku;
+
+    #[test]
+    fn only_sudoku_first_solves_ambiguous_puzzles() {
+        // The first two rows and a bit of a solved grid, which many grids share
+        let solved =
+            "483921657967345821251876493548132976729564138136798245372689514814253769695417382";
+        let puzzle = Sudoku::from_str_line(&format!("{:.<81}", &solved[..20])).unwrap();
+        for &engine in Engine::value_variants() {
+            let solution = match engine {
+                Engine::Sat => continue,
+                engine => engine.solve(puzzle).unwrap(),
+            };
+            match engine {
+                Engine::SudokuFirst => {
+                    let solution = solution.unwrap();
+                    assert!(solution.is_solved());
+                    assert!(solution.to_str_line().starts_with(&solved[..20]));
+                }
+                _ => assert_eq!(solution, None, "{}", engine),
+            }
+        }
+    }
+}
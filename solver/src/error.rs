@@ -0,0 +1,99 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use sudoku::parse_errors::LineParseError;
+
+/// Everything that can go wrong while reading and solving puzzles.
+#[derive(Debug)]
+pub enum SolverError {
+    Io(io::Error),
+    /// A line of input was not valid UTF-8
+    Encoding {
+        line: usize,
+    },
+    /// A line of input could not be parsed as a sudoku
+    Parse {
+        line: Option<usize>,
+        source: LineParseError,
+    },
+    /// A puzzle in one of the block formats was laid out wrongly
+    Format {
+        line: Option<usize>,
+        reason: String,
+    },
+    /// The puzzle parsed fine, but the engine found no unique solution
+    Unsolvable {
+        line: Option<usize>,
+    },
+}
+
+impl SolverError {
+    /// Attaches the input line number to errors that describe a single puzzle.
+    pub fn at_line(self, line_no: usize) -> Self {
+        match self {
+            SolverError::Parse { source, .. } => SolverError::Parse {
+                line: Some(line_no),
+                source,
+            },
+            SolverError::Unsolvable { .. } => SolverError::Unsolvable {
+                line: Some(line_no),
+            },
+            SolverError::Format { line: None, reason } => SolverError::Format {
+                line: Some(line_no),
+                reason,
+            },
+            other => other,
+        }
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            SolverError::Io(_) => None,
+            SolverError::Encoding { line } => Some(*line),
+            SolverError::Parse { line, .. }
+            | SolverError::Format { line, .. }
+            | SolverError::Unsolvable { line } => *line,
+        }
+    }
+
+    /// True for errors caused by the puzzle itself rather than by the input
+    /// that carried it.
+    pub fn is_unsolvable(&self) -> bool {
+        matches!(self, SolverError::Unsolvable { .. })
+    }
+}
+
+impl Display for SolverError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(line) = self.line() {
+            write!(f, "line {}: ", line)?;
+        }
+        match self {
+            SolverError::Io(e) => write!(f, "{}", e),
+            SolverError::Encoding { .. } => write!(f, "not valid UTF-8"),
+            SolverError::Parse { source, .. } => write!(f, "{}", source),
+            SolverError::Format { reason, .. } => write!(f, "{}", reason),
+            SolverError::Unsolvable { .. } => write!(f, "no unique solution"),
+        }
+    }
+}
+
+impl std::error::Error for SolverError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SolverError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SolverError {
+    fn from(e: io::Error) -> Self {
+        SolverError::Io(e)
+    }
+}
+
+impl From<LineParseError> for SolverError {
+    fn from(source: LineParseError) -> Self {
+        SolverError::Parse { line: None, source }
+    }
+}
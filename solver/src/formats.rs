@@ -0,0 +1,462 @@
+use crate::error::SolverError;
+use clap::ArgEnum;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::io::BufRead;
+use std::str::FromStr;
+use sudoku::Sudoku;
+
+/// Puzzle file formats understood by the solver, the generator and the backend.
+#[derive(ArgEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// One puzzle per line, 81 cells with `.` for blanks
+    Line,
+    /// SadMan multi-puzzle: one puzzle per line, 81 cells with `0` for blanks
+    Sdm,
+    /// SadMan single puzzle: 9 rows of 9 cells with `.` for blanks
+    Sdk,
+    /// Simple Sudoku: 9 rows split by `|` with `-` lines between boxes
+    Ss,
+    /// 9 rows of space separated cells with `0` for blanks
+    Grid,
+    /// 9 rows of comma separated cells with `0` for blanks
+    Csv,
+    /// 9 rows drawn with `+`, `-` and `|` borders around every box
+    Bordered,
+}
+
+impl Format {
+    /// Whether a single line of input holds a whole puzzle.
+    pub fn is_single_line(self) -> bool {
+        matches!(self, Format::Line | Format::Sdm)
+    }
+
+    /// Guesses the format from the first line of a puzzle. Anything whose
+    /// first token covers a whole grid is treated as one puzzle per line,
+    /// everything else as a 9 row block.
+    pub fn detect(line: &str) -> Format {
+        let trimmed = line.trim();
+        let first_token = trimmed.split_whitespace().next().unwrap_or("");
+        if first_token.chars().count() >= 81 {
+            if first_token.contains('.') {
+                Format::Line
+            } else {
+                Format::Sdm
+            }
+        } else if trimmed.starts_with('+') {
+            Format::Bordered
+        } else if trimmed.contains('|') {
+            Format::Ss
+        } else if trimmed.contains(',') {
+            Format::Csv
+        } else if trimmed.contains(' ') || trimmed.contains('\t') {
+            Format::Grid
+        } else {
+            Format::Sdk
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Format as ArgEnum>::from_str(s, true)
+    }
+}
+
+fn is_comment(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with('#') || trimmed.starts_with('[')
+}
+
+fn cell_value(ch: char) -> Option<Option<u8>> {
+    match ch {
+        '1'..='9' => Some(Some(ch as u8 - b'0')),
+        '.' | '0' | '_' | '*' | 'x' | 'X' => Some(None),
+        _ => None,
+    }
+}
+
+fn is_separator(ch: char) -> bool {
+    ch.is_whitespace() || matches!(ch, ',' | ';' | '|' | '+' | '-' | '=' | ':')
+}
+
+/// Reads the cells of one row of a block format, ignoring borders and separators.
+fn parse_row(row: &str) -> Result<Vec<u8>, String> {
+    let mut cells = Vec::with_capacity(9);
+    for (col, ch) in row.chars().enumerate() {
+        if is_separator(ch) {
+            continue;
+        }
+        match cell_value(ch) {
+            Some(value) => cells.push(value.unwrap_or(0)),
+            None => {
+                return Err(format!(
+                    "column {} contains invalid character '{}'",
+                    col + 1,
+                    ch
+                ))
+            }
+        }
+    }
+    Ok(cells)
+}
+
+/// A puzzle read from some input, tagged with the line it started on.
+#[derive(Debug)]
+pub struct Entry {
+    pub line: usize,
+    pub puzzle: Result<Sudoku, SolverError>,
+}
+
+/// Iterator over the puzzles in any supported format. The format is either
+/// given up front or detected from the first line that is not blank or a
+/// comment. Bad puzzles come back as entries holding an error so callers can
+/// report them and carry on; only I/O errors end the iteration early.
+pub struct Puzzles<R> {
+    lines: io::Split<R>,
+    line_no: usize,
+    format: Option<Format>,
+}
+
+impl<R: BufRead> Puzzles<R> {
+    pub fn new(reader: R, format: Option<Format>) -> Self {
+        Puzzles {
+            lines: reader.split(b'\n'),
+            line_no: 0,
+            format,
+        }
+    }
+
+    /// The format being read, once it is known.
+    pub fn format(&self) -> Option<Format> {
+        self.format
+    }
+
+    fn next_line(&mut self) -> Option<io::Result<Result<String, SolverError>>> {
+        let bytes = match self.lines.next()? {
+            Ok(b) => b,
+            Err(e) => return Some(Err(e)),
+        };
+        self.line_no += 1;
+        Some(Ok(match String::from_utf8(bytes) {
+            Ok(l) => Ok(l.trim_end_matches('\r').to_string()),
+            Err(_) => Err(SolverError::Encoding { line: self.line_no }),
+        }))
+    }
+
+    fn next_block(&mut self, first_line: String) -> Option<io::Result<Entry>> {
+        let start = self.line_no;
+        let mut cells: Vec<u8> = Vec::with_capacity(81);
+        let mut line = first_line;
+        loop {
+            if !is_comment(&line) {
+                match parse_row(&line) {
+                    Ok(row) if row.is_empty() => {}
+                    Ok(row) if row.len() == 9 => cells.extend(row),
+                    Ok(row) => {
+                        let error = self.format_error(format!(
+                            "expected 9 cells in a row, found {}",
+                            row.len()
+                        ));
+                        return self.skip_block(cells.len() / 9 + 1, error);
+                    }
+                    Err(reason) => {
+                        let error = self.format_error(reason);
+                        return self.skip_block(cells.len() / 9 + 1, error);
+                    }
+                }
+            }
+            if cells.len() == 81 {
+                let mut bytes = [0; 81];
+                bytes.copy_from_slice(&cells);
+                let puzzle = Sudoku::from_bytes(bytes).map_err(|_| SolverError::Format {
+                    line: Some(start),
+                    reason: "cell out of range".to_string(),
+                });
+                return Some(Ok(Entry {
+                    line: start,
+                    puzzle,
+                }));
+            }
+            line = match self.next_line() {
+                None if cells.is_empty() => return None,
+                None => {
+                    return Some(Ok(self.format_error(format!(
+                        "puzzle ends after {} of 9 rows",
+                        cells.len() / 9
+                    ))))
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok(Err(e))) => {
+                    let error = Entry {
+                        line: start,
+                        puzzle: Err(e),
+                    };
+                    return self.skip_block(cells.len() / 9 + 1, error);
+                }
+                Some(Ok(Ok(l))) => l,
+            };
+        }
+    }
+
+    /// Reads past the rest of a block that had a bad row, up to a blank line
+    /// or the end of its 9 rows, so the next puzzle starts on its own first
+    /// row. `rows` counts the rows read so far, the bad one included.
+    fn skip_block(&mut self, mut rows: usize, error: Entry) -> Option<io::Result<Entry>> {
+        while !rows.is_multiple_of(9) {
+            match self.next_line() {
+                None => break,
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok(Err(_))) => rows += 1,
+                Some(Ok(Ok(line))) if line.trim().is_empty() => break,
+                Some(Ok(Ok(line))) => {
+                    let border = matches!(parse_row(&line), Ok(row) if row.is_empty());
+                    if !is_comment(&line) && !border {
+                        rows += 1;
+                    }
+                }
+            }
+        }
+        Some(Ok(error))
+    }
+
+    fn format_error(&self, reason: String) -> Entry {
+        Entry {
+            line: self.line_no,
+            puzzle: Err(SolverError::Format {
+                line: Some(self.line_no),
+                reason,
+            }),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Puzzles<R> {
+    type Item = io::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.next_line()? {
+                Err(e) => return Some(Err(e)),
+                Ok(Err(e)) => {
+                    return Some(Ok(Entry {
+                        line: self.line_no,
+                        puzzle: Err(e),
+                    }))
+                }
+                Ok(Ok(l)) => l,
+            };
+            if line.trim().is_empty() || is_comment(&line) {
+                continue;
+            }
+            let format = *self.format.get_or_insert_with(|| Format::detect(&line));
+            if format.is_single_line() {
+                let puzzle = Sudoku::from_str_line(line.trim())
+                    .map_err(|e| SolverError::from(e).at_line(self.line_no));
+                return Some(Ok(Entry {
+                    line: self.line_no,
+                    puzzle,
+                }));
+            }
+            return self.next_block(line);
+        }
+    }
+}
+
+/// Parses every puzzle in `text`, detecting the format unless one is given.
+pub fn parse_puzzles(text: &str, format: Option<Format>) -> Vec<Entry> {
+    Puzzles::new(text.as_bytes(), format)
+        .collect::<io::Result<Vec<Entry>>>()
+        .expect("Reading from memory cannot fail")
+}
+
+/// Parses exactly one puzzle, in any supported format.
+pub fn parse_puzzle(text: &str) -> Result<Sudoku, SolverError> {
+    let mut entries = parse_puzzles(text, None).into_iter();
+    match (entries.next(), entries.next()) {
+        (Some(entry), None) => entry.puzzle,
+        (None, _) => Err(SolverError::Format {
+            line: None,
+            reason: "no puzzle found".to_string(),
+        }),
+        (Some(_), Some(_)) => Err(SolverError::Format {
+            line: None,
+            reason: "expected a single puzzle".to_string(),
+        }),
+    }
+}
+
+/// Renders a puzzle in the given format, without a trailing newline.
+pub fn write_puzzle(sudoku: &Sudoku, format: Format) -> String {
+    let bytes = sudoku.to_bytes();
+    let cell = |b: u8, blank: char| {
+        if b == 0 {
+            blank
+        } else {
+            (b'0' + b) as char
+        }
+    };
+    let rows = bytes.chunks(9);
+    match format {
+        Format::Line => bytes.iter().map(|&b| cell(b, '.')).collect(),
+        Format::Sdm => bytes.iter().map(|&b| cell(b, '0')).collect(),
+        Format::Sdk => rows
+            .map(|r| r.iter().map(|&b| cell(b, '.')).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Format::Grid | Format::Csv => {
+            let sep = if format == Format::Grid { " " } else { "," };
+            rows.map(|r| {
+                r.iter()
+                    .map(|&b| cell(b, '0').to_string())
+                    .collect::<Vec<_>>()
+                    .join(sep)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+        }
+        Format::Ss => {
+            let mut out = vec![];
+            for (idx, r) in rows.enumerate() {
+                if idx == 3 || idx == 6 {
+                    out.push("-----------".to_string());
+                }
+                let boxes: Vec<String> = r
+                    .chunks(3)
+                    .map(|b| b.iter().map(|&c| cell(c, '.')).collect())
+                    .collect();
+                out.push(boxes.join("|"));
+            }
+            out.join("\n")
+        }
+        Format::Bordered => {
+            let border = "+-------+-------+-------+";
+            let mut out = vec![border.to_string()];
+            for (idx, r) in rows.enumerate() {
+                let boxes: Vec<String> = r
+                    .chunks(3)
+                    .map(|b| {
+                        b.iter()
+                            .map(|&c| cell(c, '.').to_string())
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    })
+                    .collect();
+                out.push(format!("| {} |", boxes.join(" | ")));
+                if idx % 3 == 2 {
+                    out.push(border.to_string());
+                }
+            }
+            out.join("\n")
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_puzzle, parse_puzzles, write_puzzle, Format};
+    use sudoku::Sudoku;
+
+    const PUZZLE: &str =
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..";
+
+    const FORMATS: [Format; 7] = [
+        Format::Line,
+        Format::Sdm,
+        Format::Sdk,
+        Format::Ss,
+        Format::Grid,
+        Format::Csv,
+        Format::Bordered,
+    ];
+
+    fn puzzle() -> Sudoku {
+        Sudoku::from_str_line(PUZZLE).unwrap()
+    }
+
+    #[test]
+    fn detects_every_format() {
+        let cases = [
+            (PUZZLE, Format::Line),
+            (
+                "003020600900305001001806400008102900700000008006708200002609500800203009005010300",
+                Format::Sdm,
+            ),
+            ("..3.2.6..", Format::Sdk),
+            ("..3|.2.|6..", Format::Ss),
+            ("0 0 3 0 2 0 6 0 0", Format::Grid),
+            ("0,0,3,0,2,0,6,0,0", Format::Csv),
+            ("+-------+-------+-------+", Format::Bordered),
+        ];
+        for (line, format) in cases {
+            assert_eq!(Format::detect(line), format, "{}", line);
+        }
+    }
+
+    #[test]
+    fn round_trips_every_format() {
+        for format in FORMATS {
+            let text = write_puzzle(&puzzle(), format);
+            let first_line = text.lines().next().unwrap();
+            assert_eq!(Format::detect(first_line), format, "{:?}", format);
+            assert_eq!(parse_puzzle(&text).unwrap(), puzzle(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        let short_row = "# a comment\n..3.2.6..\n9..3.5..1\n..18.64\n";
+        let e = parse_puzzle(short_row).unwrap_err();
+        assert_eq!(e.line(), Some(4));
+        assert_eq!(e.to_string(), "line 4: expected 9 cells in a row, found 7");
+
+        let bad_char = "..3|.2.|6..\n9..|3.5|..1\n..1|8.6|4?.\n";
+        let e = parse_puzzle(bad_char).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "line 3: column 10 contains invalid character '?'"
+        );
+
+        let e = parse_puzzle("..3.2.6..\n9..3.5..1\n").unwrap_err();
+        assert_eq!(e.to_string(), "line 2: puzzle ends after 2 of 9 rows");
+
+        let entries = parse_puzzles(&format!("{}\n\n{}\n", PUZZLE, &PUZZLE[1..]), None);
+        assert!(entries[0].puzzle.is_ok());
+        assert_eq!(entries[1].line, 3);
+        assert_eq!(entries[1].puzzle.as_ref().unwrap_err().line(), Some(3));
+
+        assert_eq!(parse_puzzle("").unwrap_err().to_string(), "no puzzle found");
+        assert_eq!(
+            parse_puzzle(&format!("{}\n{}", PUZZLE, PUZZLE))
+                .unwrap_err()
+                .to_string(),
+            "expected a single puzzle"
+        );
+    }
+
+    #[test]
+    fn skips_the_rest_of_a_bad_block() {
+        let good = write_puzzle(&puzzle(), Format::Ss);
+        let mut lines: Vec<String> = good.lines().map(str::to_string).collect();
+        lines[1] = lines[1].replacen('.', "?", 1);
+        let bad = lines.join("\n");
+        let text = format!("{}\n\n{}\n\n{}\n", bad, good, good);
+        let entries = parse_puzzles(&text, Some(Format::Ss));
+        let lines: Vec<usize> = entries.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![2, 13, 25]);
+        assert_eq!(entries[0].puzzle.as_ref().unwrap_err().line(), Some(2));
+        assert_eq!(entries[1].puzzle.as_ref().unwrap(), &puzzle());
+        assert_eq!(entries[2].puzzle.as_ref().unwrap(), &puzzle());
+
+        // Without blank lines between them, blocks are told apart by their 9 rows
+        let good = write_puzzle(&puzzle(), Format::Sdk);
+        let text = format!("{}\n{}\n", good.replacen('.', "?", 1), good);
+        let entries = parse_puzzles(&text, Some(Format::Sdk));
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].line, 10);
+        assert_eq!(entries[1].puzzle.as_ref().unwrap(), &puzzle());
+    }
+}
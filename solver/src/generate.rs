@@ -0,0 +1,195 @@
+use crate::steps::grade;
+use clap::ArgEnum;
+use domain::{Difficulty, Puzzle};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use sudoku::Sudoku;
+
+/// Fresh grids tried before giving up on options that are hard to meet.
+/// Hard puzzles turn up in roughly one grid in fifty.
+const ATTEMPTS: usize = 2000;
+
+/// Cells emptied together, so the clues form a pattern.
+#[derive(ArgEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Symmetry {
+    #[default]
+    None,
+    /// Unchanged by a half turn
+    Rotational,
+    /// Unchanged by a left to right flip
+    Mirror,
+    /// Unchanged by a flip about the main diagonal
+    Diagonal,
+}
+
+/// What a generated puzzle has to look like. Everything left out is up to
+/// chance.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GenerateOptions {
+    pub difficulty: Option<Difficulty>,
+    pub symmetry: Symmetry,
+    /// Makes the outcome repeatable for the same options
+    pub seed: Option<u64>,
+    pub min_clues: Option<usize>,
+    pub max_clues: Option<usize>,
+}
+
+impl GenerateOptions {
+    /// Rejects clue ranges no puzzle can meet.
+    pub fn check(&self) -> Result<(), String> {
+        let (min, max) = (self.min_clues.unwrap_or(0), self.max_clues.unwrap_or(81));
+        if min > max {
+            return Err(format!("at least {} clues cannot be at most {}", min, max));
+        }
+        if max < 17 {
+            return Err("a puzzle with a unique solution has at least 17 clues".to_string());
+        }
+        if min > 81 {
+            return Err("a grid has 81 cells".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Generates a graded puzzle with a unique solution that meets `options`, or
+/// none when no attempt did.
+pub fn generate(options: &GenerateOptions) -> Option<Puzzle> {
+    let mut rng = match options.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let min_clues = options.min_clues.unwrap_or(0);
+    let max_clues = options.max_clues.unwrap_or(81);
+    for _ in 0..ATTEMPTS {
+        let solution = filled(&mut rng);
+        let puzzle = dig(&solution, options.symmetry, min_clues, &mut rng);
+        if puzzle.n_clues() as usize > max_clues {
+            continue;
+        }
+        let difficulty = grade(puzzle);
+        if options.difficulty.is_some_and(|wanted| wanted != difficulty) {
+            continue;
+        }
+        return Some(Puzzle {
+            difficulty: Some(difficulty.to_string()),
+            ..Puzzle::with_solution(&puzzle, &solution)
+        });
+    }
+    None
+}
+
+/// A random solved grid, filled cell by cell with digits that leave the rest
+/// solvable.
+fn filled(rng: &mut StdRng) -> Sudoku {
+    let mut cells = [0; 81];
+    let mut digits: Vec<u8> = (1..=9).collect();
+    for cell in 0..81 {
+        digits.shuffle(rng);
+        for &digit in &digits {
+            cells[cell] = digit;
+            if solvable(&cells) {
+                break;
+            }
+        }
+    }
+    Sudoku::from_bytes(cells).expect("Filled cells only hold digits")
+}
+
+fn solvable(cells: &[u8; 81]) -> bool {
+    Sudoku::from_bytes(*cells).is_ok_and(|s| s.count_at_most(1) == 1)
+}
+
+/// Empties the cells of `solution` in random order, a symmetric group at a
+/// time, as long as the solution stays unique and `min_clues` remain.
+fn dig(solution: &Sudoku, symmetry: Symmetry, min_clues: usize, rng: &mut StdRng) -> Sudoku {
+    let mut cells = solution.to_bytes();
+    let mut clues = 81;
+    let mut order: Vec<usize> = (0..81).collect();
+    order.shuffle(rng);
+    for cell in order {
+        if cells[cell] == 0 {
+            continue;
+        }
+        let group = mirrored(cell, symmetry);
+        if clues < min_clues + group.len() {
+            continue;
+        }
+        for &c in &group {
+            cells[c] = 0;
+        }
+        let puzzle = Sudoku::from_bytes(cells).expect("Emptied cells hold no digits");
+        if puzzle.count_at_most(2) == 1 {
+            clues -= group.len();
+        } else {
+            let given = solution.to_bytes();
+            for &c in &group {
+                cells[c] = given[c];
+            }
+        }
+    }
+    Sudoku::from_bytes(cells).expect("Emptied cells hold no digits")
+}
+
+/// `cell` and the cell the symmetry pairs it with, once when it is its own
+/// partner.
+fn mirrored(cell: usize, symmetry: Symmetry) -> Vec<usize> {
+    let (row, col) = (cell / 9, cell % 9);
+    let partner = match symmetry {
+        Symmetry::None => cell,
+        Symmetry::Rotational => 80 - cell,
+        Symmetry::Mirror => row * 9 + 8 - col,
+        Symmetry::Diagonal => col * 9 + row,
+    };
+    if partner == cell {
+        vec![cell]
+    } else {
+        vec![cell, partner]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{generate, mirrored, GenerateOptions, Symmetry};
+    use domain::Difficulty;
+
+    #[test]
+    fn seeds_repeat_and_symmetry_holds() {
+        let options = GenerateOptions {
+            symmetry: Symmetry::Rotational,
+            seed: Some(42),
+            ..GenerateOptions::default()
+        };
+        let puzzle = generate(&options).unwrap();
+        assert_eq!(generate(&options).unwrap().puzzle, puzzle.puzzle);
+        assert_eq!(puzzle.validate(), Ok(()));
+        let cells = puzzle.sudoku().unwrap().to_bytes();
+        for cell in 0..81 {
+            for partner in mirrored(cell, Symmetry::Rotational) {
+                assert_eq!(cells[cell] == 0, cells[partner] == 0);
+            }
+        }
+    }
+
+    #[test]
+    fn meets_the_clue_range_and_difficulty() {
+        let options = GenerateOptions {
+            difficulty: Some(Difficulty::Easy),
+            seed: Some(7),
+            min_clues: Some(30),
+            max_clues: Some(34),
+            ..GenerateOptions::default()
+        };
+        let puzzle = generate(&options).unwrap();
+        assert!((30..=34).contains(&puzzle.num_clues));
+        assert_eq!(puzzle.difficulty.as_deref(), Some("easy"));
+        assert!(GenerateOptions {
+            max_clues: Some(16),
+            ..GenerateOptions::default()
+        }
+        .check()
+        .is_err());
+    }
+}
@@ -1,2 +1,15 @@
+pub mod bitboard;
 pub mod constants;
+pub mod dlx;
+pub mod engine;
+pub mod error;
+pub mod formats;
+pub mod generate;
+pub mod pretty;
+pub mod render;
+pub mod report;
+pub mod sat;
 pub mod solver;
+pub mod steps;
+pub mod stream;
+pub mod verify;
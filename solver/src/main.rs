@@ -1,6 +1,22 @@
+use clap::{ArgEnum, Parser, Subcommand};
+use solver::engine::Engine;
+use solver::error::SolverError;
+use solver::formats::{parse_puzzle, Format, Puzzles};
+use solver::pretty;
+use solver::render;
+use solver::render::{RenderFormat, RenderOptions};
+use solver::report::{Comparison, Report, ReportFormat};
+use solver::sat;
+use solver::solver::{search_stats, solve_file, treat_grid_with};
+use solver::steps::{logical_solve, strategy_name};
+use solver::stream::{solve_one, solve_stream, write_outcome, OutputFormat};
+use solver::verify;
+use std::fs;
 use std::fs::File;
-use solver::solver;
-use clap::Parser;
+use std::io;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::process::ExitCode;
 use sudoku::Sudoku;
 
 #[derive(Parser, Debug)]
@@ -17,30 +33,497 @@ struct Args {
 
     #[clap(short, long)]
     verbose: bool,
+
+    /// Engine used to solve puzzles
+    #[clap(short, long, arg_enum, default_value = "sudoku")]
+    engine: Engine,
+
+    /// How to report timings when solving a file
+    #[clap(short, long, arg_enum, default_value = "text")]
+    report: ReportFormat,
+
+    /// Also solve the file with this engine and report speedups over --engine
+    #[clap(long, arg_enum)]
+    compare: Option<Engine>,
+
+    /// Number of worker threads used when solving a file
+    #[clap(short, long, default_value_t = 1)]
+    threads: usize,
+
+    /// Output format for solutions of --puzzle or puzzles read from stdin
+    #[clap(long, arg_enum, default_value = "line")]
+    format: OutputFormat,
+
+    /// Shorthand for --format pretty
+    #[clap(long, conflicts_with = "format")]
+    pretty: bool,
+
+    /// Print the candidates at every step of a logical solve of --puzzle
+    #[clap(long)]
+    steps: bool,
+
+    /// Add search statistics of the native solver for --puzzle and --file
+    #[clap(long)]
+    stats: bool,
+
+    /// SAT solver run by the sat engine; it must read DIMACS on stdin
+    #[clap(long, default_value = sat::DEFAULT_BINARY)]
+    sat_binary: String,
+
+    /// Format of puzzles read from --file or stdin, detected when not given
+    #[clap(short, long, arg_enum)]
+    input_format: Option<Format>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Draw puzzles as a printable SVG, or a PDF booklet
+    Render(RenderArgs),
+    /// Walk through a logical solve one step at a time, explaining each technique
+    Explain(ExplainArgs),
+    /// Write a puzzle as DIMACS CNF, or read a SAT solver's model back into a grid
+    Cnf(CnfArgs),
+    /// Check a file of solutions against the puzzles they belong to
+    Verify(VerifyArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct VerifyArgs {
+    /// File of puzzles, in any supported format
+    #[clap(short, long)]
+    puzzles: String,
+
+    /// File with the solution of each puzzle, in the same order
+    #[clap(short, long)]
+    solutions: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct CnfArgs {
+    #[clap(short, long, required_unless_present = "decode")]
+    puzzle: Option<String>,
+
+    /// Model written by a SAT solver, printed as an 81 character line
+    #[clap(short, long, conflicts_with = "puzzle")]
+    decode: Option<String>,
+
+    /// Where to write the CNF, stdout when not given
+    #[clap(short, long)]
+    output: Option<String>,
+
+    /// Also hold every digit once on both main diagonals, as in X-sudoku
+    #[clap(long)]
+    diagonals: bool,
+
+    /// Another set of nine cells that holds every digit once, as comma
+    /// separated indices from 0 to 80. Can be given more than once.
+    #[clap(long, parse(try_from_str = sat::parse_house), multiple_occurrences(true))]
+    house: Vec<[usize; 9]>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ExplainArgs {
+    #[clap(short, long)]
+    puzzle: String,
+
+    /// Print every step at once instead of waiting for Enter between steps
+    #[clap(long)]
+    all: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct RenderArgs {
+    #[clap(short, long)]
+    puzzle: Option<String>,
+
+    /// File of puzzles, in any supported format
+    #[clap(short, long)]
+    file: Option<String>,
+
+    /// Where to write the drawing, stdout when not given
+    #[clap(short, long)]
+    output: Option<String>,
+
+    /// Defaults to the extension of --output, or svg for a single puzzle
+    #[clap(long, arg_enum)]
+    format: Option<RenderFormat>,
+
+    /// Pencil in the candidates of every empty cell
+    #[clap(long)]
+    candidates: bool,
+
+    /// Add the solution, next to the puzzle in an SVG or on later PDF pages
+    #[clap(long)]
+    solution: bool,
+
+    /// Puzzles per PDF page, from 1 to 6
+    #[clap(long, default_value_t = 4)]
+    per_page: usize,
+}
+
+fn render(args: RenderArgs) -> Result<usize, SolverError> {
+    let mut puzzles: Vec<Sudoku> = vec![];
+    let mut failures = 0;
+    if let Some(p) = &args.puzzle {
+        puzzles.push(parse_puzzle(p)?);
+    }
+    if let Some(f) = &args.file {
+        for entry in Puzzles::new(io::BufReader::new(File::open(f)?), None) {
+            let entry = entry?;
+            match entry.puzzle {
+                Ok(p) => puzzles.push(p),
+                Err(e) => {
+                    eprintln!("{}", e.at_line(entry.line));
+                    failures += 1;
+                }
+            }
+        }
+    }
+    let from_extension = args.output.as_deref().and_then(|o| {
+        Path::new(o)
+            .extension()
+            .and_then(|e| RenderFormat::from_str(&e.to_string_lossy(), true).ok())
+    });
+    let format = args
+        .format
+        .or(from_extension)
+        .unwrap_or(if puzzles.len() > 1 {
+            RenderFormat::Pdf
+        } else {
+            RenderFormat::Svg
+        });
+    let options = RenderOptions {
+        candidates: args.candidates,
+        solution: args.solution,
+    };
+    let bytes = match (format, puzzles.as_slice()) {
+        (_, []) => {
+            eprintln!("Nothing to render, pass --puzzle or --file");
+            return Ok(failures + 1);
+        }
+        (RenderFormat::Svg, [puzzle]) => render::svg(puzzle, options).into_bytes(),
+        (RenderFormat::Svg, _) => {
+            eprintln!("An SVG holds a single puzzle, use --format pdf for a booklet");
+            return Ok(failures + 1);
+        }
+        (RenderFormat::Pdf, _) => render::pdf_booklet(&puzzles, args.per_page, options),
+    };
+    match args.output {
+        Some(o) => fs::write(o, bytes)?,
+        None => io::stdout().lock().write_all(&bytes)?,
+    }
+    Ok(failures)
 }
 
-fn main() {
-    let args = Args::parse();
-    match args.puzzle {
-        Some(p) => {
-            solver::treat_grid(&p, args.verbose);
+/// Prints the steps of a logical solve with their explanations. On a
+/// terminal it pauses after each step until Enter is pressed, or `q` quits.
+/// A closed output pipe ends it quietly.
+fn explain(args: ExplainArgs) -> Result<usize, SolverError> {
+    let puzzle = parse_puzzle(&args.puzzle)?;
+    let interactive = !args.all && io::stdin().is_terminal() && io::stdout().is_terminal();
+    match write_explanation(&puzzle, interactive) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(0),
+        r => Ok(r?),
+    }
+}
+
+fn write_explanation(puzzle: &Sudoku, interactive: bool) -> io::Result<usize> {
+    let colour = pretty::use_colour();
+    let puzzle = *puzzle;
+    let solve = logical_solve(puzzle);
+    let mut out = io::stdout().lock();
+    writeln!(out, "{}\n", pretty::grid(&puzzle, None, colour))?;
+    let mut used: Vec<(&str, usize)> = vec![];
+    for (idx, step) in solve.steps.iter().enumerate() {
+        let name = strategy_name(&step.strategy);
+        match used.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, count)) => *count += 1,
+            None => used.push((name, 1)),
         }
-        _ => {}
+        writeln!(
+            out,
+            "Step {} of {}: {}",
+            idx + 1,
+            solve.steps.len(),
+            step.summary()
+        )?;
+        writeln!(out, "{}", step.explanation)?;
+        writeln!(out, "{}\n", pretty::step_grid(&puzzle, step, colour))?;
+        if interactive && idx + 1 < solve.steps.len() {
+            write!(out, "Press Enter for the next step, or q to quit: ")?;
+            out.flush()?;
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            if answer.trim().eq_ignore_ascii_case("q") {
+                return Ok(0);
+            }
+        }
+    }
+    let techniques = used
+        .iter()
+        .map(|(name, count)| format!("{} x{}", name, count))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if solve.solved {
+        writeln!(
+            out,
+            "{}\n",
+            pretty::grid(&puzzle, Some(&solve.grid), colour)
+        )?;
+        writeln!(
+            out,
+            "Solved by logic in {} steps: {}",
+            solve.steps.len(),
+            techniques
+        )?;
+        Ok(0)
+    } else {
+        writeln!(
+            out,
+            "{}\n",
+            pretty::grid(&puzzle, Some(&solve.grid), colour)
+        )?;
+        writeln!(
+            out,
+            "Stuck after {} steps, the rest needs guessing. Used: {}",
+            solve.steps.len(),
+            techniques
+        )?;
+        Ok(1)
+    }
+}
+
+fn cnf(args: CnfArgs) -> Result<usize, SolverError> {
+    if let Some(model) = args.decode {
+        return match sat::decode(&fs::read_to_string(model)?) {
+            Ok(grid) => {
+                println!("{}", grid.to_str_line());
+                Ok(0)
+            }
+            Err(reason) => Err(SolverError::Format { line: None, reason }),
+        };
     }
-    match args.file {
-        Some(f) => {
-            solver::solve_file(File::open(f).expect("File must exist"), args.verbose)
+    let puzzle = parse_puzzle(args.puzzle.as_deref().unwrap_or_default())?;
+    let mut houses = args.house;
+    if args.diagonals {
+        houses.extend(sat::DIAGONALS);
+    }
+    let dimacs = format!(
+        "c sudoku {}\n{}",
+        puzzle.to_str_line(),
+        sat::encode(&puzzle, &houses).to_dimacs()
+    );
+    match args.output {
+        Some(o) => fs::write(o, dimacs)?,
+        None => io::stdout().lock().write_all(dimacs.as_bytes())?,
+    }
+    Ok(0)
+}
+
+/// Pairs every puzzle with the solution at the same position and prints
+/// what is wrong with each pair, returning how many pairs had problems.
+fn verify(args: VerifyArgs) -> Result<usize, SolverError> {
+    let open = |path: &str| -> Result<_, SolverError> {
+        Ok(Puzzles::new(io::BufReader::new(File::open(path)?), None))
+    };
+    let mut puzzles = open(&args.puzzles)?;
+    let mut solutions = open(&args.solutions)?;
+    let mut out = io::stdout().lock();
+    let (mut checked, mut failures) = (0, 0);
+    loop {
+        let problems: Vec<String> = match (puzzles.next(), solutions.next()) {
+            (None, None) => break,
+            (Some(puzzle), None) => vec![format!(
+                "line {}: no solution for this puzzle",
+                puzzle?.line
+            )],
+            (None, Some(solution)) => vec![format!(
+                "line {}: solution without a puzzle",
+                solution?.line
+            )],
+            (Some(puzzle), Some(solution)) => {
+                let (puzzle, solution) = (puzzle?, solution?);
+                match (puzzle.puzzle, solution.puzzle) {
+                    (Err(e), _) => vec![format!("puzzle {}", e.at_line(puzzle.line))],
+                    (_, Err(e)) => vec![format!("solution {}", e.at_line(solution.line))],
+                    (Ok(p), Ok(s)) => verify::check(&p, &s)
+                        .iter()
+                        .map(|m| format!("line {}: {}", puzzle.line, m))
+                        .collect(),
+                }
+            }
+        };
+        checked += 1;
+        if !problems.is_empty() {
+            failures += 1;
+        }
+        for problem in problems {
+            writeln!(out, "{}", problem)?;
         }
-        _ => {}
     }
+    if failures == 0 {
+        writeln!(out, "All {} solutions check out", checked)?;
+    } else {
+        writeln!(out, "{} of {} pairs have problems", failures, checked)?;
+    }
+    Ok(failures)
+}
 
-    match args.count {
-        Some(count) => {
-            for _ in 0..count {
-                let s = Sudoku::generate_unique();
-                println!("{}", s);
+/// Runs the requested work, returning how many puzzles failed.
+fn run(args: Args) -> Result<usize, SolverError> {
+    match args.command {
+        Some(Command::Render(render_args)) => return render(render_args),
+        Some(Command::Explain(explain_args)) => return explain(explain_args),
+        Some(Command::Cnf(cnf_args)) => return cnf(cnf_args),
+        Some(Command::Verify(verify_args)) => return verify(verify_args),
+        None => {}
+    }
+    sat::set_binary(&args.sat_binary);
+    if args.engine == Engine::Sat || args.compare == Some(Engine::Sat) {
+        sat::check()?;
+    }
+    let format = if args.pretty {
+        OutputFormat::Pretty
+    } else {
+        args.format
+    };
+    let mut failures = 0;
+    if args.puzzle.is_none() && args.file.is_none() && args.count.is_none() {
+        let stdin = io::stdin();
+        let stats = solve_stream(
+            stdin.lock(),
+            &mut io::stdout().lock(),
+            args.engine,
+            args.input_format,
+            format,
+        )?;
+        if stats.failed > 0 {
+            eprintln!(
+                "Failed on {} of {} puzzles",
+                stats.failed,
+                stats.failed + stats.solved
+            );
+        }
+        return Ok(stats.failed);
+    }
+    if let Some(p) = args.puzzle {
+        if args.steps {
+            match print_steps(&parse_puzzle(&p)?) {
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(failures),
+                r => r?,
             }
         }
-        _ => {}
+        if args.verbose {
+            let (_, solved) = treat_grid_with(args.engine, &p, args.verbose)?;
+            if !solved {
+                failures += 1;
+            }
+        } else {
+            let outcome = solve_one(args.engine, &p);
+            let puzzle = parse_puzzle(&p).ok();
+            match write_outcome(&mut io::stdout().lock(), format, puzzle.as_ref(), &outcome) {
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(failures),
+                r => r?,
+            }
+            if let Err(e) = outcome {
+                eprintln!("{}", e);
+                failures += 1;
+            }
+        }
+        if args.stats {
+            eprintln!("Search: {}", search_stats(&parse_puzzle(&p)?));
+        }
+    }
+    if let Some(f) = args.file {
+        let baseline = solve_file(
+            File::open(&f)?,
+            args.engine,
+            args.input_format,
+            args.threads,
+            args.verbose,
+            args.stats,
+        )?;
+        report_failures(&baseline);
+        failures += baseline.summary.failures + baseline.summary.invalid;
+        let mut out = io::stdout().lock();
+        match args.compare {
+            Some(candidate) => {
+                let candidate = solve_file(
+                    File::open(&f)?,
+                    candidate,
+                    args.input_format,
+                    args.threads,
+                    args.verbose,
+                    false,
+                )?;
+                if !candidate.is_clean() {
+                    eprintln!("Candidate engine {}:", candidate.engine);
+                    report_failures(&candidate);
+                }
+                failures += candidate.summary.failures + candidate.summary.invalid;
+                Comparison::new(baseline, candidate).write(args.report, &mut out)?;
+            }
+            None => baseline.write(args.report, &mut out)?,
+        }
+    }
+
+    if let Some(count) = args.count {
+        for _ in 0..count {
+            let s = Sudoku::generate_unique();
+            println!("{}", s);
+        }
+    }
+    Ok(failures)
+}
+
+fn print_steps(puzzle: &Sudoku) -> io::Result<()> {
+    let colour = pretty::use_colour();
+    let solve = logical_solve(*puzzle);
+    let mut out = io::stdout().lock();
+    for (idx, step) in solve.steps.iter().enumerate() {
+        writeln!(out, "Step {}: {}", idx + 1, step.summary())?;
+        writeln!(out, "{}\n", pretty::step_grid(puzzle, step, colour))?;
+    }
+    if solve.solved {
+        writeln!(out, "Solved by logic in {} steps\n", solve.steps.len())
+    } else {
+        writeln!(
+            out,
+            "Stuck after {} steps, the rest needs guessing:",
+            solve.steps.len()
+        )?;
+        writeln!(out, "{}\n", pretty::grid(puzzle, Some(&solve.grid), colour))
+    }
+}
+
+fn report_failures(report: &Report) {
+    for invalid in &report.invalid {
+        eprintln!("{}", invalid.error);
+    }
+    for unsolved in report.results.iter().filter(|r| !r.solved) {
+        eprintln!("line {}: no unique solution", unsolved.line);
+    }
+    if !report.is_clean() {
+        eprintln!(
+            "{} of {} lines failed: {} invalid, {} unsolved",
+            report.summary.invalid + report.summary.failures,
+            report.summary.invalid + report.summary.puzzles,
+            report.summary.invalid,
+            report.summary.failures
+        );
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(0) => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
     }
 }
@@ -0,0 +1,152 @@
+use crate::steps::Step;
+use std::env;
+use std::io;
+use std::io::IsTerminal;
+use sudoku::Sudoku;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+
+/// Colour is used when stdout is a terminal, unless `NO_COLOR` is set.
+pub fn use_colour() -> bool {
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+fn paint(text: &str, style: &str, colour: bool) -> String {
+    if colour {
+        format!("{}{}{}", style, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Horizontal rule of a frame, using `ends` for the left edge, thin cross,
+/// thick cross and right edge.
+fn rule(width: usize, ends: [char; 4], fill: char) -> String {
+    let [left, thin, thick, right] = ends;
+    let mut out = String::new();
+    out.push(left);
+    for col in 0..9 {
+        out.extend(std::iter::repeat_n(fill, width));
+        out.push(match col {
+            8 => right,
+            2 | 5 => thick,
+            _ => thin,
+        });
+    }
+    out
+}
+
+/// Draws a 9x9 frame with double lines around the boxes. Every cell is
+/// `width` characters wide and `height` lines tall; `cell(idx, line)` must
+/// return exactly `width` visible characters.
+fn frame<F: Fn(usize, usize) -> String>(width: usize, height: usize, cell: F) -> String {
+    let mut out = vec![rule(width, ['╔', '╤', '╦', '╗'], '═')];
+    for row in 0..9 {
+        for line in 0..height {
+            let mut text = String::from("║");
+            for col in 0..9 {
+                text.push_str(&cell(row * 9 + col, line));
+                text.push(if col % 3 == 2 { '║' } else { '│' });
+            }
+            out.push(text);
+        }
+        out.push(match row {
+            8 => rule(width, ['╚', '╧', '╩', '╝'], '═'),
+            2 | 5 => rule(width, ['╠', '╪', '╬', '╣'], '═'),
+            _ => rule(width, ['╟', '┼', '╫', '╢'], '─'),
+        });
+    }
+    out.join("\n")
+}
+
+/// A given digit padded to `width`: in bold with colour, in brackets without
+/// it, so givens stand out from solved digits either way.
+fn given(digit: u8, width: usize, colour: bool) -> String {
+    let text = if colour {
+        digit.to_string()
+    } else {
+        format!("[{}]", digit)
+    };
+    paint(&format!("{:^width$}", text, width = width), BOLD, colour)
+}
+
+/// A boxed grid with the givens in bold (bracketed without colour) and, when
+/// a solution is given, the digits the solver filled in highlighted. The
+/// solution may be partial.
+pub fn grid(puzzle: &Sudoku, solution: Option<&Sudoku>, colour: bool) -> String {
+    let givens = puzzle.to_bytes();
+    let filled = solution.map(|s| s.to_bytes());
+    frame(3, 1, |idx, _| match (givens[idx], filled) {
+        (0, Some(f)) if f[idx] != 0 => paint(&format!(" {} ", f[idx]), GREEN, colour),
+        (0, _) => paint(" . ", DIM, colour),
+        (digit, _) => given(digit, 3, colour),
+    })
+}
+
+/// The grid before `step` with the candidates of every empty cell pencilled
+/// in, showing the digit placed in green and the candidates removed in red.
+/// Digits placed by earlier steps are shown in cyan.
+pub fn step_grid(puzzle: &Sudoku, step: &Step, colour: bool) -> String {
+    let givens = puzzle.to_bytes();
+    let cells = step.grid.to_bytes();
+    frame(7, 3, |idx, line| {
+        if givens[idx] != 0 {
+            return match line {
+                1 => given(givens[idx], 7, colour),
+                _ => " ".repeat(7),
+            };
+        }
+        if cells[idx] != 0 {
+            let digit = if line == 1 {
+                format!("   {}   ", cells[idx])
+            } else {
+                " ".repeat(7)
+            };
+            return paint(&digit, CYAN, colour);
+        }
+        let mut text = String::from(" ");
+        for digit in (line * 3 + 1)..=(line * 3 + 3) {
+            let mark = if step.candidates[idx][digit - 1] {
+                digit.to_string()
+            } else {
+                " ".to_string()
+            };
+            let key = (idx, digit as u8);
+            text.push_str(&if step.placed == Some(key) {
+                paint(&mark, GREEN, colour)
+            } else if step.eliminated.contains(&key) {
+                paint(&mark, RED, colour)
+            } else {
+                paint(&mark, DIM, colour)
+            });
+            text.push(' ');
+        }
+        text
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::grid;
+    use sudoku::Sudoku;
+
+    #[test]
+    fn brackets_givens_without_colour() {
+        let puzzle = Sudoku::from_str_line(
+            "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..",
+        )
+        .unwrap();
+        let solution = puzzle.solve_unique().unwrap();
+        let frame = grid(&puzzle, Some(&solution), false);
+        let lines: Vec<&str> = frame.lines().collect();
+        assert_eq!(lines.len(), 19);
+        assert!(lines.iter().all(|l| l.chars().count() == 37));
+        assert!(!frame.contains('\x1b'));
+        assert_eq!(lines[1], "║ 4 │ 8 │[3]║ 9 │[2]│ 1 ║[6]│ 5 │ 7 ║");
+    }
+}
@@ -0,0 +1,498 @@
+use crate::constants::ADJACENT_VALUES;
+use clap::ArgEnum;
+use std::fmt::Write;
+use sudoku::Sudoku;
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderFormat {
+    Svg,
+    Pdf,
+}
+
+/// What to draw in each grid besides the givens.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Pencil marks for every empty cell, worked out from the givens
+    pub candidates: bool,
+    /// A second grid (or, for PDFs, pages) with the solution filled in
+    pub solution: bool,
+}
+
+const THIN: f64 = 0.75;
+const THICK: f64 = 2.5;
+const SOLVED_COLOUR: (f64, f64, f64) = (0.2, 0.4, 0.8);
+const CANDIDATE_COLOUR: (f64, f64, f64) = (0.45, 0.45, 0.45);
+
+/// Width of a digit in Helvetica, as a fraction of the font size.
+const DIGIT_WIDTH: f64 = 0.556;
+
+/// Minimal drawing surface shared by the SVG and PDF back-ends. Coordinates
+/// are in points with the origin at the top left.
+trait Canvas {
+    fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, width: f64);
+    /// Draws `text` with its baseline at `y`, horizontally centred on `x`.
+    fn text(&mut self, x: f64, y: f64, size: f64, bold: bool, colour: (f64, f64, f64), text: &str);
+}
+
+/// Digits that can still go in each cell, judging only by the givens.
+pub fn candidates(sudoku: &Sudoku) -> [[bool; 9]; 81] {
+    let cells = sudoku.to_bytes();
+    let mut candidates = [[false; 9]; 81];
+    for (idx, possible) in candidates.iter_mut().enumerate() {
+        if cells[idx] != 0 {
+            continue;
+        }
+        *possible = [true; 9];
+        for &peer in ADJACENT_VALUES[idx].iter() {
+            if cells[peer] != 0 {
+                possible[cells[peer] as usize - 1] = false;
+            }
+        }
+    }
+    candidates
+}
+
+fn draw_grid<C: Canvas>(
+    canvas: &mut C,
+    x: f64,
+    y: f64,
+    size: f64,
+    puzzle: &Sudoku,
+    filled: Option<&Sudoku>,
+    show_candidates: bool,
+) {
+    let cell = size / 9.0;
+    for i in 0..=9 {
+        let width = if i % 3 == 0 { THICK } else { THIN };
+        let offset = i as f64 * cell;
+        canvas.line(x + offset, y, x + offset, y + size, width);
+        canvas.line(x, y + offset, x + size, y + offset, width);
+    }
+    let givens = puzzle.to_bytes();
+    let solved = filled.map(|s| s.to_bytes());
+    let marks = if show_candidates && filled.is_none() {
+        Some(candidates(puzzle))
+    } else {
+        None
+    };
+    for idx in 0..81 {
+        let left = x + (idx % 9) as f64 * cell;
+        let top = y + (idx / 9) as f64 * cell;
+        let font = cell * 0.62;
+        let baseline = top + cell / 2.0 + font * 0.36;
+        if givens[idx] != 0 {
+            let digit = givens[idx].to_string();
+            canvas.text(
+                left + cell / 2.0,
+                baseline,
+                font,
+                true,
+                (0.0, 0.0, 0.0),
+                &digit,
+            );
+        } else if let Some(solved) = solved {
+            let digit = solved[idx].to_string();
+            canvas.text(
+                left + cell / 2.0,
+                baseline,
+                font,
+                false,
+                SOLVED_COLOUR,
+                &digit,
+            );
+        } else if let Some(marks) = marks {
+            let small = cell / 3.0;
+            for (d, _) in marks[idx].iter().enumerate().filter(|(_, &p)| p) {
+                let cx = left + (d % 3) as f64 * small + small / 2.0;
+                let cy = top + (d / 3) as f64 * small + small / 2.0 + small * 0.25;
+                let digit = (d + 1).to_string();
+                canvas.text(cx, cy, small * 0.7, false, CANDIDATE_COLOUR, &digit);
+            }
+        }
+    }
+}
+
+struct Svg {
+    body: String,
+}
+
+fn svg_colour((r, g, b): (f64, f64, f64)) -> String {
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        (r * 255.0) as u8,
+        (g * 255.0) as u8,
+        (b * 255.0) as u8
+    )
+}
+
+impl Canvas for Svg {
+    fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, width: f64) {
+        let _ = writeln!(
+            self.body,
+            r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" stroke="black" stroke-width="{}" stroke-linecap="square"/>"#,
+            x1, y1, x2, y2, width
+        );
+    }
+
+    fn text(&mut self, x: f64, y: f64, size: f64, bold: bool, colour: (f64, f64, f64), text: &str) {
+        let _ = writeln!(
+            self.body,
+            r#"<text x="{:.2}" y="{:.2}" font-size="{:.2}" text-anchor="middle" fill="{}"{}>{}</text>"#,
+            x,
+            y,
+            size,
+            svg_colour(colour),
+            if bold { r#" font-weight="bold""# } else { "" },
+            text
+        );
+    }
+}
+
+/// Draws a single puzzle as a standalone SVG document. With
+/// [`RenderOptions::solution`] the solved grid is drawn to the right of it.
+pub fn svg(puzzle: &Sudoku, options: RenderOptions) -> String {
+    let size = 360.0;
+    let margin = 12.0;
+    let solution = if options.solution {
+        puzzle.solve_one()
+    } else {
+        None
+    };
+    let grids = if solution.is_some() { 2.0 } else { 1.0 };
+    let width = grids * size + (grids + 1.0) * margin;
+    let height = size + 2.0 * margin;
+    let mut canvas = Svg {
+        body: String::new(),
+    };
+    draw_grid(
+        &mut canvas,
+        margin,
+        margin,
+        size,
+        puzzle,
+        None,
+        options.candidates,
+    );
+    if let Some(solution) = solution {
+        draw_grid(
+            &mut canvas,
+            size + 2.0 * margin,
+            margin,
+            size,
+            puzzle,
+            Some(&solution),
+            false,
+        );
+    }
+    format!(
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="Helvetica, Arial, sans-serif">"#,
+            "\n",
+            r#"<rect width="{w}" height="{h}" fill="white"/>"#,
+            "\n{body}</svg>\n"
+        ),
+        w = width,
+        h = height,
+        body = canvas.body
+    )
+}
+
+const PAGE_WIDTH: f64 = 595.0;
+const PAGE_HEIGHT: f64 = 842.0;
+const PAGE_MARGIN: f64 = 48.0;
+
+struct PdfPage {
+    content: String,
+}
+
+impl Canvas for PdfPage {
+    fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, width: f64) {
+        let _ = writeln!(
+            self.content,
+            "{} w 2 J {:.2} {:.2} m {:.2} {:.2} l S",
+            width,
+            x1,
+            PAGE_HEIGHT - y1,
+            x2,
+            PAGE_HEIGHT - y2
+        );
+    }
+
+    fn text(&mut self, x: f64, y: f64, size: f64, bold: bool, colour: (f64, f64, f64), text: &str) {
+        let width = text.chars().count() as f64 * DIGIT_WIDTH * size;
+        let escaped = text
+            .replace('\\', "\\\\")
+            .replace('(', "\\(")
+            .replace(')', "\\)");
+        let _ = writeln!(
+            self.content,
+            "BT {:.3} {:.3} {:.3} rg /{} {:.2} Tf {:.2} {:.2} Td ({}) Tj ET",
+            colour.0,
+            colour.1,
+            colour.2,
+            if bold { "F2" } else { "F1" },
+            size,
+            x - width / 2.0,
+            PAGE_HEIGHT - y,
+            escaped
+        );
+    }
+}
+
+/// Number of columns and rows used to fit `per_page` grids on a page.
+fn layout(per_page: usize) -> (usize, usize) {
+    let columns = if per_page <= 2 { 1 } else { 2 };
+    (columns, per_page.div_ceil(columns))
+}
+
+fn booklet_pages(
+    grids: &[(String, Sudoku, Option<Sudoku>)],
+    per_page: usize,
+    candidates: bool,
+) -> Vec<String> {
+    let (columns, rows) = layout(per_page);
+    let title_height = 20.0;
+    let gap = 24.0;
+    let slot_width = (PAGE_WIDTH - 2.0 * PAGE_MARGIN - (columns - 1) as f64 * gap) / columns as f64;
+    let slot_height = (PAGE_HEIGHT - 2.0 * PAGE_MARGIN - (rows - 1) as f64 * gap) / rows as f64;
+    let size = slot_width.min(slot_height - title_height);
+    grids
+        .chunks(per_page)
+        .map(|chunk| {
+            let mut page = PdfPage {
+                content: String::new(),
+            };
+            for (slot, (title, puzzle, solution)) in chunk.iter().enumerate() {
+                let x = PAGE_MARGIN
+                    + (slot % columns) as f64 * (slot_width + gap)
+                    + (slot_width - size) / 2.0;
+                let y = PAGE_MARGIN + (slot / columns) as f64 * (slot_height + gap);
+                page.text(
+                    x + size / 2.0,
+                    y + title_height * 0.6,
+                    12.0,
+                    true,
+                    (0.0, 0.0, 0.0),
+                    title,
+                );
+                draw_grid(
+                    &mut page,
+                    x,
+                    y + title_height,
+                    size,
+                    puzzle,
+                    solution.as_ref(),
+                    candidates,
+                );
+            }
+            page.content
+        })
+        .collect()
+}
+
+/// Lays out `puzzles` into a printable A4 PDF with `per_page` grids on each
+/// page, followed by solution pages in the same order when asked for.
+pub fn pdf_booklet(puzzles: &[Sudoku], per_page: usize, options: RenderOptions) -> Vec<u8> {
+    let per_page = per_page.clamp(1, 6);
+    let puzzle_grids: Vec<_> = puzzles
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (format!("Puzzle {}", i + 1), *p, None))
+        .collect();
+    let mut pages = booklet_pages(&puzzle_grids, per_page, options.candidates);
+    if options.solution {
+        let solution_grids: Vec<_> = puzzles
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| {
+                p.solve_one()
+                    .map(|s| (format!("Solution {}", i + 1), *p, Some(s)))
+            })
+            .collect();
+        pages.extend(booklet_pages(&solution_grids, per_page.max(4), false));
+    }
+    write_pdf(&pages)
+}
+
+/// Serialises page content streams into a PDF file using the two built-in
+/// Helvetica fonts, so no font data has to be embedded.
+fn write_pdf(pages: &[String]) -> Vec<u8> {
+    let mut objects: Vec<String> = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        String::new(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold >>".to_string(),
+    ];
+    let mut kids = vec![];
+    for content in pages {
+        let page_id = objects.len() + 1;
+        kids.push(format!("{} 0 R", page_id));
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH,
+            PAGE_HEIGHT,
+            page_id + 1
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            content.len(),
+            content
+        ));
+    }
+    objects[1] = format!(
+        "<< /Type /Pages /Kids [{}] /Count {} >>",
+        kids.join(" "),
+        pages.len()
+    );
+
+    let mut out = String::from("%PDF-1.4\n");
+    let mut offsets = vec![];
+    for (idx, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        let _ = write!(out, "{} 0 obj\n{}\nendobj\n", idx + 1, object);
+    }
+    let xref = out.len();
+    let _ = write!(out, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(out, "{:010} 00000 n ", offset);
+    }
+    let _ = write!(
+        out,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    );
+    out.into_bytes()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{candidates, pdf_booklet, svg, RenderOptions};
+    use sudoku::Sudoku;
+
+    const PUZZLE: &str =
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..";
+
+    /// Fill colour and content of every `<text>` element.
+    fn texts(svg: &str) -> Vec<(&str, &str)> {
+        svg.lines()
+            .filter(|l| l.starts_with("<text"))
+            .map(|l| {
+                let fill = l
+                    .split("fill=\"")
+                    .nth(1)
+                    .unwrap()
+                    .split('"')
+                    .next()
+                    .unwrap();
+                let content = l.split('>').nth(1).unwrap().trim_end_matches("</text");
+                (fill, content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn svg_draws_the_grid_and_the_givens() {
+        let puzzle = Sudoku::from_str_line(PUZZLE).unwrap();
+        let out = svg(&puzzle, RenderOptions::default());
+        assert!(out.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(out.ends_with("</svg>\n"));
+        // Ten lines each way divide the grid into 81 cells
+        assert_eq!(out.matches("<line").count(), 20);
+        let givens: Vec<String> = PUZZLE
+            .chars()
+            .filter(|c| c.is_ascii_digit())
+            .map(String::from)
+            .collect();
+        let drawn: Vec<(&str, &str)> = texts(&out);
+        assert_eq!(drawn.len(), givens.len());
+        assert!(drawn.iter().all(|(fill, _)| *fill == "#000000"));
+        assert_eq!(
+            drawn.iter().map(|(_, digit)| *digit).collect::<Vec<_>>(),
+            givens
+        );
+        assert_eq!(out.matches("font-weight=\"bold\"").count(), givens.len());
+    }
+
+    #[test]
+    fn svg_adds_candidates_and_the_solution() {
+        let puzzle = Sudoku::from_str_line(PUZZLE).unwrap();
+        let clues = puzzle.n_clues() as usize;
+        let marked = svg(
+            &puzzle,
+            RenderOptions {
+                candidates: true,
+                ..RenderOptions::default()
+            },
+        );
+        let marks: usize = candidates(&puzzle)
+            .iter()
+            .map(|c| c.iter().filter(|&&p| p).count())
+            .sum();
+        let drawn = texts(&marked);
+        assert_eq!(drawn.iter().filter(|(f, _)| *f == "#727272").count(), marks);
+        assert_eq!(drawn.len(), clues + marks);
+
+        let solved = svg(
+            &puzzle,
+            RenderOptions {
+                solution: true,
+                ..RenderOptions::default()
+            },
+        );
+        assert_eq!(solved.matches("<line").count(), 40);
+        let drawn = texts(&solved);
+        let filled: Vec<&str> = drawn
+            .iter()
+            .filter(|(f, _)| *f == "#3366cc")
+            .map(|(_, d)| *d)
+            .collect();
+        assert_eq!(filled.len(), 81 - clues);
+        assert_eq!(drawn.len(), 2 * clues + 81 - clues);
+        let solution = puzzle.solve_one().unwrap().to_str_line().to_string();
+        let expected: Vec<String> = PUZZLE
+            .chars()
+            .zip(solution.chars())
+            .filter(|(given, _)| *given == '.')
+            .map(|(_, digit)| digit.to_string())
+            .collect();
+        assert_eq!(filled, expected);
+    }
+
+    #[test]
+    fn pdf_has_a_valid_cross_reference_table() {
+        let puzzle = Sudoku::from_str_line(PUZZLE).unwrap();
+        let options = RenderOptions {
+            solution: true,
+            ..RenderOptions::default()
+        };
+        let bytes = pdf_booklet(&[puzzle; 3], 2, options);
+        let pdf = String::from_utf8(bytes).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        assert!(pdf.ends_with("%%EOF\n"));
+        // Two pages of puzzles, then one page holding all three solutions
+        assert!(pdf.contains("/Count 3 >>"));
+
+        let startxref: usize = pdf
+            .rsplit("startxref\n")
+            .next()
+            .unwrap()
+            .lines()
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut table = pdf[startxref..].lines();
+        assert_eq!(table.next(), Some("xref"));
+        let size: usize = table.next().unwrap()[2..].parse().unwrap();
+        assert_eq!(table.next(), Some("0000000000 65535 f "));
+        for object in 1..size {
+            let entry = table.next().unwrap();
+            assert!(entry.ends_with(" 00000 n "));
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(&format!("{} 0 obj\n", object)));
+        }
+        assert_eq!(table.next(), Some("trailer"));
+        assert!(pdf.contains(&format!("/Size {} /Root 1 0 R", size)));
+    }
+}
@@ -0,0 +1,504 @@
+use crate::engine::Engine;
+use crate::error::SolverError;
+use crate::solver::SearchStats;
+use clap::ArgEnum;
+use serde::Serialize;
+use std::io;
+use std::io::Write;
+use std::time::Duration;
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+/// Outcome of solving a single line of a puzzle file.
+#[derive(Serialize, Clone, Debug)]
+pub struct PuzzleResult {
+    pub line: usize,
+    pub duration_ns: u64,
+    pub solved: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<SearchStats>,
+}
+
+impl PuzzleResult {
+    pub fn new(line: usize, duration: Duration, solved: bool) -> Self {
+        PuzzleResult {
+            line,
+            duration_ns: nanos(duration),
+            solved,
+            stats: None,
+        }
+    }
+
+    pub fn with_stats(mut self, stats: Option<SearchStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+}
+
+/// A line of the input that never made it to the engine.
+#[derive(Serialize, Clone, Debug)]
+pub struct InvalidLine {
+    pub line: Option<usize>,
+    pub error: String,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Summary {
+    pub puzzles: usize,
+    pub invalid: usize,
+    pub solved: usize,
+    pub failures: usize,
+    pub total_ns: u64,
+    pub mean_ns: Option<u64>,
+    pub min_ns: Option<u64>,
+    pub max_ns: Option<u64>,
+    pub p50_ns: Option<u64>,
+    pub p90_ns: Option<u64>,
+    pub p99_ns: Option<u64>,
+    pub puzzles_per_sec: Option<f64>,
+}
+
+/// Search statistics of the native solver, added up over a whole file.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct SearchSummary {
+    pub nodes: u64,
+    pub guesses: u64,
+    pub propagations: u64,
+    pub max_depth: u32,
+    pub mean_guesses: Option<f64>,
+    pub max_guesses: u64,
+    /// Puzzles that could not be finished by propagation alone
+    pub guessed: usize,
+}
+
+impl SearchSummary {
+    pub fn from_results(results: &[PuzzleResult]) -> Option<Self> {
+        let stats: Vec<&SearchStats> = results.iter().filter_map(|r| r.stats.as_ref()).collect();
+        if stats.is_empty() {
+            return None;
+        }
+        let guesses: u64 = stats.iter().map(|s| s.guesses).sum();
+        Some(SearchSummary {
+            nodes: stats.iter().map(|s| s.nodes).sum(),
+            guesses,
+            propagations: stats.iter().map(|s| s.propagations).sum(),
+            max_depth: stats.iter().map(|s| s.max_depth).max().unwrap_or_default(),
+            mean_guesses: Some(guesses as f64 / stats.len() as f64),
+            max_guesses: stats.iter().map(|s| s.guesses).max().unwrap_or_default(),
+            guessed: stats.iter().filter(|s| s.guesses > 0).count(),
+        })
+    }
+}
+
+/// Wall-clock view of a run, as opposed to the summed per-puzzle solve times
+/// in [`Summary`], which stay the same no matter how many threads were used.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Throughput {
+    pub threads: usize,
+    pub wall_ns: u64,
+    pub puzzles_per_sec: Option<f64>,
+}
+
+/// Timings for one engine over a whole puzzle file.
+#[derive(Serialize, Clone, Debug)]
+pub struct Report {
+    pub engine: Engine,
+    pub summary: Summary,
+    pub throughput: Throughput,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search: Option<SearchSummary>,
+    pub results: Vec<PuzzleResult>,
+    pub invalid: Vec<InvalidLine>,
+}
+
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+fn per_sec(puzzles: usize, total_ns: u64) -> Option<f64> {
+    if total_ns == 0 {
+        None
+    } else {
+        Some(puzzles as f64 * 1e9 / total_ns as f64)
+    }
+}
+
+/// Nearest-rank percentile over an already sorted slice.
+fn percentile(sorted: &[u64], p: u64) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p as usize * sorted.len()).div_ceil(100);
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+impl Summary {
+    pub fn from_results(results: &[PuzzleResult]) -> Self {
+        let mut durations: Vec<u64> = results.iter().map(|r| r.duration_ns).collect();
+        durations.sort_unstable();
+        let total_ns: u64 = durations.iter().sum();
+        let solved = results.iter().filter(|r| r.solved).count();
+        Summary {
+            puzzles: results.len(),
+            invalid: 0,
+            solved,
+            failures: results.len() - solved,
+            total_ns,
+            mean_ns: total_ns.checked_div(durations.len() as u64),
+            min_ns: durations.first().copied(),
+            max_ns: durations.last().copied(),
+            p50_ns: percentile(&durations, 50),
+            p90_ns: percentile(&durations, 90),
+            p99_ns: percentile(&durations, 99),
+            puzzles_per_sec: per_sec(durations.len(), total_ns),
+        }
+    }
+}
+
+impl Report {
+    pub fn new(engine: Engine, results: Vec<PuzzleResult>) -> Self {
+        let summary = Summary::from_results(&results);
+        Report {
+            engine,
+            throughput: Throughput {
+                threads: 1,
+                wall_ns: summary.total_ns,
+                puzzles_per_sec: summary.puzzles_per_sec,
+            },
+            summary,
+            search: SearchSummary::from_results(&results),
+            results,
+            invalid: vec![],
+        }
+    }
+
+    pub fn with_invalid(mut self, errors: &[SolverError]) -> Self {
+        self.invalid = errors
+            .iter()
+            .map(|e| InvalidLine {
+                line: e.line(),
+                error: e.to_string(),
+            })
+            .collect();
+        self.summary.invalid = self.invalid.len();
+        self
+    }
+
+    /// True when every line of the input was parsed and solved.
+    pub fn is_clean(&self) -> bool {
+        self.summary.failures == 0 && self.summary.invalid == 0
+    }
+
+    pub fn with_wall_time(mut self, wall_time: Duration, threads: usize) -> Self {
+        let wall_ns = nanos(wall_time);
+        self.throughput = Throughput {
+            threads,
+            wall_ns,
+            puzzles_per_sec: per_sec(self.results.len(), wall_ns),
+        };
+        self
+    }
+
+    pub fn write<W: Write>(&self, format: ReportFormat, out: &mut W) -> io::Result<()> {
+        match format {
+            ReportFormat::Text => {
+                write_text_summary(&self.summary, out)?;
+                write_text_throughput(&self.throughput, out)?;
+                match &self.search {
+                    Some(search) => write_text_search(search, self.summary.puzzles, out),
+                    None => Ok(()),
+                }
+            }
+            ReportFormat::Json => {
+                serde_json::to_writer_pretty(&mut *out, self)?;
+                writeln!(out)
+            }
+            ReportFormat::Csv => {
+                write!(out, "line,engine,duration_ns,solved")?;
+                if self.search.is_some() {
+                    write!(out, ",nodes,guesses,max_depth,propagations")?;
+                }
+                writeln!(out)?;
+                for r in &self.results {
+                    write!(
+                        out,
+                        "{},{},{},{}",
+                        r.line, self.engine, r.duration_ns, r.solved
+                    )?;
+                    if let Some(s) = &r.stats {
+                        write!(
+                            out,
+                            ",{},{},{},{}",
+                            s.nodes, s.guesses, s.max_depth, s.propagations
+                        )?;
+                    }
+                    writeln!(out)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn write_text_summary<W: Write>(summary: &Summary, out: &mut W) -> io::Result<()> {
+    if summary.puzzles == 0 {
+        writeln!(out, "No puzzles found")?;
+    } else {
+        write_text_timings(summary, out)?;
+    }
+    if summary.failures > 0 {
+        writeln!(out, "Failed to solve {} puzzles", summary.failures)?;
+    }
+    if summary.invalid > 0 {
+        writeln!(out, "Skipped {} invalid lines", summary.invalid)?;
+    }
+    Ok(())
+}
+
+fn write_text_timings<W: Write>(summary: &Summary, out: &mut W) -> io::Result<()> {
+    writeln!(
+        out,
+        "Spent {} s in total for solving {} puzzles. avg per puzzle = {} ns or roughly {} puzzles/sec",
+        summary.total_ns / 1_000_000_000,
+        summary.puzzles,
+        summary.mean_ns.unwrap_or_default(),
+        summary
+            .puzzles_per_sec
+            .map(|p| format!("{:.0}", p))
+            .unwrap_or_else(|| "n/a".to_string())
+    )?;
+    writeln!(
+        out,
+        "Slowest puzzle took {} ns",
+        summary.max_ns.unwrap_or_default()
+    )?;
+    writeln!(
+        out,
+        "Fastest puzzle took {} ns",
+        summary.min_ns.unwrap_or_default()
+    )?;
+    writeln!(
+        out,
+        "Percentiles: p50 = {} ns, p90 = {} ns, p99 = {} ns",
+        summary.p50_ns.unwrap_or_default(),
+        summary.p90_ns.unwrap_or_default(),
+        summary.p99_ns.unwrap_or_default()
+    )
+}
+
+fn write_text_throughput<W: Write>(throughput: &Throughput, out: &mut W) -> io::Result<()> {
+    if throughput.threads <= 1 {
+        return Ok(());
+    }
+    writeln!(
+        out,
+        "Wall clock {} ms on {} threads, roughly {} puzzles/sec",
+        throughput.wall_ns / 1_000_000,
+        throughput.threads,
+        throughput
+            .puzzles_per_sec
+            .map(|p| format!("{:.0}", p))
+            .unwrap_or_else(|| "n/a".to_string())
+    )
+}
+
+fn write_text_search<W: Write>(
+    search: &SearchSummary,
+    puzzles: usize,
+    out: &mut W,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "Search: {} nodes, {} guesses (mean {:.1}, max {} per puzzle), max depth {}, {} propagations",
+        search.nodes,
+        search.guesses,
+        search.mean_guesses.unwrap_or_default(),
+        search.max_guesses,
+        search.max_depth,
+        search.propagations
+    )?;
+    writeln!(
+        out,
+        "{} of {} puzzles needed guessing",
+        search.guessed, puzzles
+    )
+}
+
+fn ratio(baseline: Option<u64>, candidate: Option<u64>) -> Option<f64> {
+    match (baseline, candidate) {
+        (Some(b), Some(c)) if c > 0 => Some(b as f64 / c as f64),
+        _ => None,
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Speedup {
+    pub total: Option<f64>,
+    pub mean: Option<f64>,
+    pub p50: Option<f64>,
+    pub p90: Option<f64>,
+    pub p99: Option<f64>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PuzzleComparison {
+    pub line: usize,
+    pub baseline_ns: u64,
+    pub candidate_ns: u64,
+    pub speedup: Option<f64>,
+    pub agree: bool,
+}
+
+/// Two engines run over the same file. Speedups are baseline / candidate,
+/// so a value above 1 means the candidate engine was faster.
+#[derive(Serialize, Clone, Debug)]
+pub struct Comparison {
+    pub baseline: Engine,
+    pub candidate: Engine,
+    pub baseline_summary: Summary,
+    pub candidate_summary: Summary,
+    pub baseline_throughput: Throughput,
+    pub candidate_throughput: Throughput,
+    pub speedup: Speedup,
+    pub puzzles: Vec<PuzzleComparison>,
+}
+
+impl Comparison {
+    pub fn new(baseline: Report, candidate: Report) -> Self {
+        let b = &baseline.summary;
+        let c = &candidate.summary;
+        let speedup = Speedup {
+            total: ratio(Some(b.total_ns), Some(c.total_ns)),
+            mean: ratio(b.mean_ns, c.mean_ns),
+            p50: ratio(b.p50_ns, c.p50_ns),
+            p90: ratio(b.p90_ns, c.p90_ns),
+            p99: ratio(b.p99_ns, c.p99_ns),
+        };
+        let puzzles = baseline
+            .results
+            .iter()
+            .zip(candidate.results.iter())
+            .map(|(b, c)| PuzzleComparison {
+                line: b.line,
+                baseline_ns: b.duration_ns,
+                candidate_ns: c.duration_ns,
+                speedup: ratio(Some(b.duration_ns), Some(c.duration_ns)),
+                agree: b.solved == c.solved,
+            })
+            .collect();
+        Comparison {
+            baseline: baseline.engine,
+            candidate: candidate.engine,
+            baseline_summary: baseline.summary,
+            candidate_summary: candidate.summary,
+            baseline_throughput: baseline.throughput,
+            candidate_throughput: candidate.throughput,
+            speedup,
+            puzzles,
+        }
+    }
+
+    pub fn write<W: Write>(&self, format: ReportFormat, out: &mut W) -> io::Result<()> {
+        match format {
+            ReportFormat::Text => {
+                writeln!(out, "Baseline engine: {}", self.baseline)?;
+                write_text_summary(&self.baseline_summary, out)?;
+                write_text_throughput(&self.baseline_throughput, out)?;
+                writeln!(out, "Candidate engine: {}", self.candidate)?;
+                write_text_summary(&self.candidate_summary, out)?;
+                write_text_throughput(&self.candidate_throughput, out)?;
+                let fmt = |s: Option<f64>| {
+                    s.map(|s| format!("{:.2}x", s))
+                        .unwrap_or_else(|| "n/a".to_string())
+                };
+                writeln!(
+                    out,
+                    "Speedup of {} over {}: total {}, mean {}, p50 {}, p90 {}, p99 {}",
+                    self.candidate,
+                    self.baseline,
+                    fmt(self.speedup.total),
+                    fmt(self.speedup.mean),
+                    fmt(self.speedup.p50),
+                    fmt(self.speedup.p90),
+                    fmt(self.speedup.p99)
+                )?;
+                let disagreements = self.puzzles.iter().filter(|p| !p.agree).count();
+                if disagreements > 0 {
+                    writeln!(out, "Engines disagreed on {} puzzles", disagreements)?;
+                }
+                Ok(())
+            }
+            ReportFormat::Json => {
+                serde_json::to_writer_pretty(&mut *out, self)?;
+                writeln!(out)
+            }
+            ReportFormat::Csv => {
+                writeln!(out, "line,baseline_ns,candidate_ns,speedup,agree")?;
+                for p in &self.puzzles {
+                    writeln!(
+                        out,
+                        "{},{},{},{},{}",
+                        p.line,
+                        p.baseline_ns,
+                        p.candidate_ns,
+                        p.speedup.map(|s| format!("{:.4}", s)).unwrap_or_default(),
+                        p.agree
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{percentile, PuzzleResult, Summary};
+    use std::time::Duration;
+
+    fn result(line: usize, ns: u64, solved: bool) -> PuzzleResult {
+        PuzzleResult::new(line, Duration::from_nanos(ns), solved)
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let sorted: Vec<u64> = (1..=10).collect();
+        assert_eq!(percentile(&sorted, 50), Some(5));
+        assert_eq!(percentile(&sorted, 90), Some(9));
+        assert_eq!(percentile(&sorted, 99), Some(10));
+        assert_eq!(percentile(&sorted, 0), Some(1));
+        assert_eq!(percentile(&[7], 99), Some(7));
+        assert_eq!(percentile(&[], 50), None);
+    }
+
+    #[test]
+    fn summarises_results() {
+        let results = [
+            result(1, 300, true),
+            result(2, 100, false),
+            result(3, 200, true),
+        ];
+        let summary = Summary::from_results(&results);
+        assert_eq!(
+            (summary.puzzles, summary.solved, summary.failures),
+            (3, 2, 1)
+        );
+        assert_eq!(summary.total_ns, 600);
+        assert_eq!(summary.mean_ns, Some(200));
+        assert_eq!((summary.min_ns, summary.max_ns), (Some(100), Some(300)));
+        assert_eq!(summary.p50_ns, Some(200));
+        assert_eq!(summary.p99_ns, Some(300));
+        assert_eq!(summary.puzzles_per_sec, Some(5e6));
+    }
+
+    #[test]
+    fn summarises_no_results_without_panicking() {
+        let summary = Summary::from_results(&[]);
+        assert_eq!(summary, Summary::default());
+    }
+}
@@ -0,0 +1,281 @@
+use std::fmt::Write as _;
+use std::io;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use sudoku::Sudoku;
+
+static BINARY: OnceLock<String> = OnceLock::new();
+
+/// SAT solver used when none was configured.
+pub const DEFAULT_BINARY: &str = "kissat";
+
+/// Sets the SAT solver used by [`solve_unique`]. Only the first call has an
+/// effect.
+pub fn set_binary(path: &str) {
+    let _ = BINARY.set(path.to_string());
+}
+
+fn binary() -> &'static str {
+    BINARY.get().map(|b| b.as_str()).unwrap_or(DEFAULT_BINARY)
+}
+
+/// A formula in conjunctive normal form, with DIMACS style literals.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cnf {
+    pub variables: usize,
+    pub clauses: Vec<Vec<i32>>,
+}
+
+impl Cnf {
+    pub fn to_dimacs(&self) -> String {
+        let mut out = format!("p cnf {} {}\n", self.variables, self.clauses.len());
+        for clause in &self.clauses {
+            for literal in clause {
+                let _ = write!(out, "{} ", literal);
+            }
+            out.push_str("0\n");
+        }
+        out
+    }
+}
+
+/// The two main diagonals, the extra houses of X-sudoku.
+pub const DIAGONALS: [[usize; 9]; 2] = [
+    [0, 10, 20, 30, 40, 50, 60, 70, 80],
+    [8, 16, 24, 32, 40, 48, 56, 64, 72],
+];
+
+/// Reads an extra house written as nine comma separated cell indices, 0 to
+/// 80 in reading order.
+pub fn parse_house(text: &str) -> Result<[usize; 9], String> {
+    let cells = text
+        .split(',')
+        .map(|c| match c.trim().parse::<usize>() {
+            Ok(cell) if cell < 81 => Ok(cell),
+            _ => Err(format!("'{}' is not a cell between 0 and 80", c.trim())),
+        })
+        .collect::<Result<Vec<usize>, String>>()?;
+    let mut house: [usize; 9] = cells
+        .as_slice()
+        .try_into()
+        .map_err(|_| format!("a house has 9 cells, not {}", cells.len()))?;
+    house.sort_unstable();
+    if house.windows(2).any(|w| w[0] == w[1]) {
+        return Err("a house cannot hold a cell twice".to_string());
+    }
+    Ok(house)
+}
+
+/// The variable that is true when `cell` holds `digit`.
+pub fn variable(cell: usize, digit: u8) -> i32 {
+    (cell * 9 + digit as usize) as i32
+}
+
+fn exactly_one(cnf: &mut Cnf, literals: &[i32]) {
+    cnf.clauses.push(literals.to_vec());
+    for (idx, &a) in literals.iter().enumerate() {
+        for &b in &literals[idx + 1..] {
+            cnf.clauses.push(vec![-a, -b]);
+        }
+    }
+}
+
+/// Encodes `sudoku` as CNF: every cell holds one digit, and every row,
+/// column, box and entry of `extra_houses` holds each digit once.
+pub fn encode(sudoku: &Sudoku, extra_houses: &[[usize; 9]]) -> Cnf {
+    let mut cnf = Cnf {
+        variables: 729,
+        clauses: vec![],
+    };
+    for cell in 0..81 {
+        let digits: Vec<i32> = (1..=9).map(|d| variable(cell, d)).collect();
+        exactly_one(&mut cnf, &digits);
+    }
+    let mut houses: Vec<[usize; 9]> = vec![];
+    for i in 0..9 {
+        let mut row = [0; 9];
+        let mut col = [0; 9];
+        let mut block = [0; 9];
+        for j in 0..9 {
+            row[j] = i * 9 + j;
+            col[j] = j * 9 + i;
+            block[j] = (i / 3 * 3 + j / 3) * 9 + i % 3 * 3 + j % 3;
+        }
+        houses.extend([row, col, block]);
+    }
+    houses.extend_from_slice(extra_houses);
+    for house in &houses {
+        for digit in 1..=9 {
+            let cells: Vec<i32> = house.iter().map(|&c| variable(c, digit)).collect();
+            exactly_one(&mut cnf, &cells);
+        }
+    }
+    for (cell, &given) in sudoku.to_bytes().iter().enumerate() {
+        if given != 0 {
+            cnf.clauses.push(vec![variable(cell, given)]);
+        }
+    }
+    cnf
+}
+
+/// Reads a model, either as `v` lines in SAT competition format or as the
+/// bare list of literals some solvers write to a file, back into a grid.
+pub fn decode(model: &str) -> Result<Sudoku, String> {
+    let mut bytes = [0u8; 81];
+    for line in model.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('c') || line.starts_with('s') || line == "SAT" {
+            continue;
+        }
+        for token in line.trim_start_matches('v').split_whitespace() {
+            let literal: i32 = token
+                .parse()
+                .map_err(|_| format!("'{}' is not a literal", token))?;
+            if !(1..=729).contains(&literal) {
+                continue;
+            }
+            let cell = (literal as usize - 1) / 9;
+            let digit = ((literal - 1) % 9 + 1) as u8;
+            if bytes[cell] != 0 && bytes[cell] != digit {
+                return Err(format!(
+                    "cell {} holds both {} and {}",
+                    cell, bytes[cell], digit
+                ));
+            }
+            bytes[cell] = digit;
+        }
+    }
+    if bytes.contains(&0) {
+        return Err("model leaves cells empty".to_string());
+    }
+    Sudoku::from_bytes(bytes).map_err(|_| "model is not a grid".to_string())
+}
+
+/// Runs the SAT solver on `cnf`. It must read DIMACS from stdin and answer
+/// in SAT competition format, as kissat, cadical and cryptominisat do.
+fn run(cnf: &Cnf) -> io::Result<Option<String>> {
+    let mut child = Command::new(binary())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("cannot run {}: {}", binary(), e)))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let dimacs = cnf.to_dimacs();
+    let writer = thread::spawn(move || stdin.write_all(dimacs.as_bytes()));
+    let output = child.wait_with_output()?;
+    let written = writer.join().expect("Writer thread panicked");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.lines().any(|l| l.trim() == "s SATISFIABLE") {
+        Ok(Some(stdout.into_owned()))
+    } else if stdout.lines().any(|l| l.trim() == "s UNSATISFIABLE") {
+        Ok(None)
+    } else {
+        written?;
+        Err(io::Error::other(format!(
+            "{} gave no answer ({})",
+            binary(),
+            output.status
+        )))
+    }
+}
+
+/// Makes sure the configured SAT solver runs and can solve a trivial formula.
+pub fn check() -> io::Result<()> {
+    let cnf = Cnf {
+        variables: 1,
+        clauses: vec![vec![1]],
+    };
+    match run(&cnf)? {
+        Some(_) => Ok(()),
+        None => Err(io::Error::other(format!(
+            "{} claims a trivial formula is unsatisfiable",
+            binary()
+        ))),
+    }
+}
+
+/// Solves `sudoku` with the external SAT solver. A second call, with the
+/// first solution ruled out, proves it unique.
+pub fn solve_unique(sudoku: &Sudoku) -> io::Result<Option<Sudoku>> {
+    let mut cnf = encode(sudoku, &[]);
+    let solution = match run(&cnf)? {
+        Some(model) => decode(&model).map_err(io::Error::other)?,
+        None => return Ok(None),
+    };
+    let blocking = solution
+        .to_bytes()
+        .iter()
+        .enumerate()
+        .map(|(cell, &digit)| -variable(cell, digit))
+        .collect();
+    cnf.clauses.push(blocking);
+    match run(&cnf)? {
+        Some(_) => Ok(None),
+        None => Ok(Some(solution)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, encode, parse_house, variable, DIAGONALS};
+    use sudoku::Sudoku;
+
+    const PUZZLE: &str =
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..";
+
+    #[test]
+    fn encodes_givens_and_decodes_a_model() {
+        let puzzle = Sudoku::from_str_line(PUZZLE).unwrap();
+        let cnf = encode(&puzzle, &[]);
+        assert_eq!(cnf.variables, 729);
+        assert!(cnf.clauses.contains(&vec![variable(2, 3)]));
+        assert!(cnf
+            .to_dimacs()
+            .starts_with(&format!("p cnf 729 {}\n", cnf.clauses.len())));
+
+        // A model as a solver would print it: every variable, true or false
+        let solution = puzzle.solve_unique().unwrap();
+        let cells = solution.to_bytes();
+        let literals: Vec<String> = (0..81)
+            .flat_map(|cell| {
+                (1..=9u8).map(move |d| {
+                    let v = variable(cell, d);
+                    if cells[cell] == d { v } else { -v }.to_string()
+                })
+            })
+            .collect();
+        let model = format!("s SATISFIABLE\nv {}\nv 0\n", literals.join(" "));
+        assert_eq!(decode(&model), Ok(solution));
+        assert!(decode("s SATISFIABLE\nv 1 0\n").is_err());
+        assert!(decode("v 1 2 0\n").unwrap_err().contains("holds both"));
+    }
+
+    #[test]
+    fn extra_houses_add_clauses() {
+        let empty = Sudoku::from_bytes([0; 81]).unwrap();
+        // Per digit and house: one clause that some cell has it, and 36 that
+        // no two cells do
+        let per_house = 9 * (1 + 36);
+        let cells = 81 * (1 + 36);
+        let plain = encode(&empty, &[]);
+        assert_eq!(plain.clauses.len(), cells + 27 * per_house);
+        let x = encode(&empty, &DIAGONALS);
+        assert_eq!(x.clauses.len(), plain.clauses.len() + 2 * per_house);
+        let given = Sudoku::from_str_line(PUZZLE).unwrap();
+        assert_eq!(
+            encode(&given, &DIAGONALS).clauses.len(),
+            x.clauses.len() + given.n_clues() as usize
+        );
+    }
+
+    #[test]
+    fn parses_houses() {
+        assert_eq!(parse_house("80, 70,60,50,40,30,20,10,0"), Ok(DIAGONALS[0]));
+        assert!(parse_house("0,1,2").is_err());
+        assert!(parse_house("0,1,2,3,4,5,6,7,81").is_err());
+        assert!(parse_house("0,1,2,3,4,5,6,7,7").is_err());
+    }
+}
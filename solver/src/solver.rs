@@ -1,47 +1,264 @@
+use crate::constants::{ADJACENT_CELLS, ADJACENT_VALUES};
+use crate::engine::Engine;
+use crate::error::SolverError;
+use crate::formats::{parse_puzzle, Format, Puzzles};
+use crate::report::{PuzzleResult, Report};
+use crate::steps::grade;
+use domain::Puzzle;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io;
-use std::io::BufRead;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 use std::time::{Duration, Instant};
 use sudoku::parse_errors::LineParseError;
 use sudoku::Sudoku;
 
+#[cfg(test)]
 fn parse_grid(grid_string: &str) -> Result<Sudoku, LineParseError> {
     Sudoku::from_str_line(grid_string)
 }
 
-fn solve_sudoku(sudoku: Sudoku) -> Option<Sudoku> {
-    sudoku.solve_unique()
+pub fn treat_grid(grid_string: &str, verbose: bool) -> Result<Duration, SolverError> {
+    treat_grid_with(Engine::default(), grid_string, verbose).map(|(duration, _)| duration)
+}
+
+/// Solves a single puzzle, in any supported format, with the given engine, returning how long the engine
+/// spent and whether it found a solution.
+pub fn treat_grid_with(
+    engine: Engine,
+    grid_string: &str,
+    verbose: bool,
+) -> Result<(Duration, bool), SolverError> {
+    let (solved, duration, _) = time_grid(engine, parse_puzzle(grid_string)?, false)?;
+    if verbose {
+        print_outcome(&solved, duration);
+    }
+    Ok((duration, solved.is_some()))
 }
 
-pub fn treat_grid(grid_string: &str, verbose: bool) -> Duration {
-    let sudoku = Sudoku::from_str_line(grid_string).expect("Should only pass string if valid");
+type Timed = (Option<Sudoku>, Duration, Option<SearchStats>);
+
+/// Times the engine on `sudoku`. Search statistics come from a separate,
+/// untimed run of the native solver, so they never skew the timings.
+fn time_grid(engine: Engine, sudoku: Sudoku, stats: bool) -> Result<Timed, SolverError> {
     let now = Instant::now();
-    let solved = solve_sudoku(sudoku);
+    let solved = engine.solve(sudoku)?;
     let duration = now.elapsed();
-    if verbose {
-        match solved {
-            Some(s) => {
-                println!("Grid complete ! in {} us", (duration.as_micros()));
-                println!("{}", s.to_str_line());
+    Ok((solved, duration, stats.then(|| search_stats(&sudoku))))
+}
+
+fn print_outcome(solved: &Option<Sudoku>, duration: Duration) {
+    match solved {
+        Some(s) => {
+            println!("Grid complete ! in {} us", (duration.as_micros()));
+            println!("{}", s.to_str_line());
+        }
+        None => {
+            println!("Couldn't solve the puzzle in {} us", duration.as_micros());
+        }
+    }
+}
+
+/// A cell of the native solver's grid: either a placed digit or the digits
+/// it could still hold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellValue {
+    Value(u8),
+    Possibilities([bool; 9]),
+}
+
+pub type Grid = [CellValue; 81];
+
+pub fn empty_grid() -> Grid {
+    [CellValue::Possibilities([true; 9]); 81]
+}
+
+pub fn grid_from_sudoku(sudoku: &Sudoku) -> Grid {
+    let mut grid = empty_grid();
+    for (cell, &value) in sudoku.to_bytes().iter().enumerate() {
+        if value != 0 {
+            grid[cell] = CellValue::Value(value);
+        }
+    }
+    grid
+}
+
+pub fn grid_to_sudoku(grid: &Grid) -> Sudoku {
+    let mut bytes = [0; 81];
+    for (cell, value) in grid.iter().enumerate() {
+        if let CellValue::Value(v) = value {
+            bytes[cell] = *v;
+        }
+    }
+    Sudoku::from_bytes(bytes).expect("Grid cells only hold digits 1-9")
+}
+
+/// How much work the native solver did on a puzzle.
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// Grids examined by the backtracking search, including the first
+    pub nodes: u64,
+    /// Digits tried in a cell that had more than one candidate
+    pub guesses: u64,
+    /// Guesses that had to be undone, having led to no solution
+    pub backtracks: u64,
+    /// Deepest nesting of guesses
+    pub max_depth: u32,
+    /// Digits placed because a cell or a house had one option left
+    pub propagations: u64,
+}
+
+impl Display for SearchStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} nodes, {} guesses, {} backtracks, max depth {}, {} propagations",
+            self.nodes, self.guesses, self.backtracks, self.max_depth, self.propagations
+        )
+    }
+}
+
+/// Solves `grid` with constraint propagation and backtracking, returning the
+/// solution only when it is unique.
+pub fn solve_grid(grid: Grid) -> Option<Grid> {
+    solve_grid_with_stats(grid).0
+}
+
+/// Like [`solve_grid`], but also reports how hard the search had to work.
+pub fn solve_grid_with_stats(grid: Grid) -> (Option<Grid>, SearchStats) {
+    let mut stats = SearchStats::default();
+    let mut start = empty_grid();
+    for (cell, value) in grid.iter().enumerate() {
+        if let CellValue::Value(v) = value {
+            if !assign(&mut start, cell, *v, &mut stats) {
+                return (None, stats);
             }
-            None => {
-                println!("Couldn't solve the puzzle in {} us", duration.as_micros());
+        }
+    }
+    let mut solutions = Vec::with_capacity(2);
+    search(start, 0, &mut stats, &mut solutions);
+    match solutions.as_slice() {
+        [solution] => (Some(*solution), stats),
+        _ => (None, stats),
+    }
+}
+
+/// Search statistics of the native solver for `sudoku`.
+pub fn search_stats(sudoku: &Sudoku) -> SearchStats {
+    solve_grid_with_stats(grid_from_sudoku(sudoku)).1
+}
+
+/// Places `value` in `cell` and removes it from every peer, following any
+/// singles this uncovers. Returns false on a contradiction.
+fn assign(grid: &mut Grid, cell: usize, value: u8, stats: &mut SearchStats) -> bool {
+    let possible = match grid[cell] {
+        CellValue::Value(v) => return v == value,
+        CellValue::Possibilities(p) if !p[value as usize - 1] => return false,
+        CellValue::Possibilities(p) => p,
+    };
+    grid[cell] = CellValue::Value(value);
+    ADJACENT_VALUES[cell]
+        .iter()
+        .all(|&peer| eliminate(grid, peer, value, stats))
+        && (1..=9u8)
+            .filter(|&d| d != value && possible[d as usize - 1])
+            .all(|d| place_hidden_singles(grid, cell, d, stats))
+}
+
+fn eliminate(grid: &mut Grid, cell: usize, value: u8, stats: &mut SearchStats) -> bool {
+    let possible = match &mut grid[cell] {
+        CellValue::Value(v) => return *v != value,
+        CellValue::Possibilities(p) if !p[value as usize - 1] => return true,
+        CellValue::Possibilities(p) => {
+            p[value as usize - 1] = false;
+            *p
+        }
+    };
+    let mut left = (1..=9u8).filter(|d| possible[*d as usize - 1]);
+    match (left.next(), left.next()) {
+        (None, _) => return false,
+        (Some(only), None) => {
+            stats.propagations += 1;
+            if !assign(grid, cell, only, stats) {
+                return false;
             }
         }
+        _ => {}
     }
-    duration
+    place_hidden_singles(grid, cell, value, stats)
 }
 
-pub struct Puzzle {
-    pub puzzle: String,
-    pub solution: String,
+/// After `value` was ruled out of `cell`, places it in any house of `cell`
+/// that has a single spot left for it.
+fn place_hidden_singles(grid: &mut Grid, cell: usize, value: u8, stats: &mut SearchStats) -> bool {
+    for house in ADJACENT_CELLS[cell].iter() {
+        if house
+            .iter()
+            .any(|&other| grid[other] == CellValue::Value(value))
+        {
+            continue;
+        }
+        let mut places = house.iter().filter(
+            |&&other| matches!(grid[other], CellValue::Possibilities(p) if p[value as usize - 1]),
+        );
+        match (places.next().copied(), places.next()) {
+            (None, _) => return false,
+            (Some(only), None) => {
+                stats.propagations += 1;
+                if !assign(grid, only, value, stats) {
+                    return false;
+                }
+            }
+            _ => {}
+        }
+    }
+    true
+}
+
+/// Depth-first search over the cell with the fewest candidates, stopping
+/// once a second solution shows the puzzle is not unique.
+fn search(grid: Grid, depth: u32, stats: &mut SearchStats, solutions: &mut Vec<Grid>) {
+    stats.nodes += 1;
+    stats.max_depth = stats.max_depth.max(depth);
+    let choice = grid
+        .iter()
+        .enumerate()
+        .filter_map(|(cell, value)| match value {
+            CellValue::Possibilities(p) => Some((cell, *p)),
+            CellValue::Value(_) => None,
+        })
+        .min_by_key(|(_, p)| p.iter().filter(|&&b| b).count());
+    let (cell, possible) = match choice {
+        Some(c) => c,
+        None => {
+            solutions.push(grid);
+            return;
+        }
+    };
+    for digit in (1..=9u8).filter(|d| possible[*d as usize - 1]) {
+        stats.guesses += 1;
+        let found = solutions.len();
+        let mut next = grid;
+        if assign(&mut next, cell, digit, stats) {
+            search(next, depth + 1, stats, solutions);
+        }
+        if solutions.len() == found {
+            stats.backtracks += 1;
+        }
+        if solutions.len() > 1 {
+            return;
+        }
+    }
 }
 
+/// A new puzzle with a unique solution, graded.
 pub fn generate_puzzle() -> Puzzle {
     let sudoku = Sudoku::generate_unique();
     Puzzle {
-        puzzle: sudoku.to_string(),
-        solution: sudoku.solve_unique().expect("").to_string(),
+        difficulty: Some(grade(sudoku).to_string()),
+        ..Puzzle::from_sudoku(&sudoku).expect("Generated puzzles are unique")
     }
 }
 
@@ -49,42 +266,99 @@ pub fn from_string(puzzle: &str) -> Result<Sudoku, LineParseError> {
     Sudoku::from_str_line(puzzle)
 }
 
-pub fn solve_file(f: File, verbose: bool) {
-    let lines = io::BufReader::new(f).lines();
-    let mut durations: Vec<Duration> = vec![];
-    for line in lines {
-        if let Ok(l) = line {
-            durations.push(treat_grid(&l, verbose));
+/// Number of lines a worker claims at a time when solving a file in parallel.
+const BATCH_SIZE: usize = 64;
+
+/// Solves every puzzle in `f`, spreading the work over `threads` worker threads.
+/// The file format is detected unless `format` is given. Results (and verbose
+/// output) always come back in file order. Puzzles that cannot be read or
+/// parsed are recorded in the report and skipped; only an I/O error on the
+/// file itself aborts the run. With `stats`, every result also carries the
+/// native solver's search statistics.
+pub fn solve_file(
+    f: File,
+    engine: Engine,
+    format: Option<Format>,
+    threads: usize,
+    verbose: bool,
+    stats: bool,
+) -> Result<Report, SolverError> {
+    let mut puzzles: Vec<(usize, Sudoku)> = vec![];
+    let mut invalid: Vec<SolverError> = vec![];
+    for entry in Puzzles::new(io::BufReader::new(f), format) {
+        let entry = entry?;
+        match entry.puzzle {
+            Ok(p) => puzzles.push((entry.line, p)),
+            Err(e) => invalid.push(e.at_line(entry.line)),
         }
     }
-    let micros = durations
-        .iter()
-        .map(|d| d.as_nanos())
-        .collect::<Vec<u128>>();
-    let totaltime: u128 = micros.iter().sum();
-    let number_of_puzzles: u128 =
-        u128::try_from(micros.len()).expect("usize to u128 should be fine");
-    let max: &u128 = micros.iter().max().expect("There should be durations");
-    let min: &u128 = micros.iter().min().expect("There should be durations");
-    println!("Spent {} s in total for solving {} puzzles. avg per puzzle = {} ns or roughly {} puzzles/sec", totaltime / 1_000_000_000, micros.len(), totaltime / number_of_puzzles, 1_000_000_000 / (totaltime / number_of_puzzles));
-    println!("Slowest puzzle took {} ns", max);
-    println!("Fastest puzzle took {} ns", min);
+    let threads = threads.clamp(1, puzzles.len().max(1));
+    let started = Instant::now();
+    let outcomes = if threads == 1 {
+        puzzles
+            .iter()
+            .map(|(_, p)| time_grid(engine, *p, stats))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        solve_parallel(engine, &puzzles, threads, stats)?
+    };
+    let wall_time = started.elapsed();
+    let mut results: Vec<PuzzleResult> = Vec::with_capacity(outcomes.len());
+    for ((line, _), (solved, duration, search)) in puzzles.iter().zip(outcomes) {
+        if verbose {
+            print_outcome(&solved, duration);
+        }
+        results.push(PuzzleResult::new(*line, duration, solved.is_some()).with_stats(search));
+    }
+    Ok(Report::new(engine, results)
+        .with_invalid(&invalid)
+        .with_wall_time(wall_time, threads))
+}
+
+fn solve_parallel(
+    engine: Engine,
+    puzzles: &[(usize, Sudoku)],
+    threads: usize,
+    stats: bool,
+) -> Result<Vec<Timed>, SolverError> {
+    let next = AtomicUsize::new(0);
+    let mut outcomes: Vec<(usize, Result<Timed, SolverError>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut solved = vec![];
+                    loop {
+                        let start = next.fetch_add(BATCH_SIZE, Ordering::Relaxed);
+                        if start >= puzzles.len() {
+                            break solved;
+                        }
+                        let end = (start + BATCH_SIZE).min(puzzles.len());
+                        for (idx, (_, p)) in puzzles.iter().enumerate().take(end).skip(start) {
+                            solved.push((idx, time_grid(engine, *p, stats)));
+                        }
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().expect("Solver thread panicked"))
+            .collect()
+    });
+    outcomes.sort_unstable_by_key(|(idx, _)| *idx);
+    outcomes.into_iter().map(|(_, outcome)| outcome).collect()
 }
 
 #[cfg(test)]
 mod test {
+    use super::{
+        grid_from_sudoku, grid_to_sudoku, parse_grid, search_stats, solve_grid, solve_parallel,
+        time_grid, BATCH_SIZE,
+    };
+    use crate::engine::Engine;
     use std::fs::File;
     use std::io;
     use std::io::BufRead;
-    use sudoku::Sudoku;
-
-    use crate::solver::CellValue;
-    use crate::solver::{empty_grid, solve_grid, Grid};
-
-    use super::parse_grid;
-    fn empty_grid() -> Grid {
-        [CellValue::Possibilities([true; 9]); 81]
-    }
 
     #[test]
     fn an_empty_grid_can_be_parsed() {
@@ -109,28 +383,68 @@ mod test {
         let challenge =
             "081672439792843651364591782438957216256184973179326845845219367913768524627435198";
         let parsed = parse_grid(challenge).expect("Valid sudoku should give a sudoku board");
-        let solved = parsed.solve_unique();
-        assert!(match solved {
-            Some(g) => {
-                let s = g[0];
-                let n = g[1];
-                println!("{}", s);
-                println!("{}", n);
-                true
-            }
-            _ => false,
-        });
+        let solved = solve_grid(grid_from_sudoku(&parsed)).map(|g| grid_to_sudoku(&g));
+        assert_eq!(
+            solved.map(|s| s.to_str_line().to_string()),
+            Some(challenge.replacen('0', "5", 1))
+        );
+        let stats = search_stats(&parsed);
+        assert_eq!((stats.guesses, stats.backtracks), (0, 0));
     }
+
     #[test]
     fn can_solve_top_95_from_norvig() {
-        let lines =
-            io::BufReader::new(File::open("top95.txt").expect("File needs to be present")).lines();
+        let lines = io::BufReader::new(
+            File::open("../sudokus/top95.txt").expect("File needs to be present"),
+        )
+        .lines();
         for line in lines {
-            if let Ok(l) = line {
-                let parsed = parse_grid(&*l);
-                let solved = solve_grid(parsed);
-                assert!(solved.is_some())
-            }
+            let line = line.expect("File should be readable");
+            let parsed = parse_grid(&line).expect("Valid sudoku should give a sudoku board");
+            let native = Engine::Native.solve(parsed).unwrap();
+            assert!(native.is_some(), "{}", line);
+            assert_eq!(native, parsed.solve_unique(), "{}", line);
         }
     }
+
+    #[test]
+    fn counts_guesses_and_backtracks() {
+        // Norvig's hardest example, which propagation alone does not crack
+        let hard = parse_grid(
+            "4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......",
+        )
+        .expect("Valid sudoku should give a sudoku board");
+        let stats = search_stats(&hard);
+        assert!(stats.backtracks > 0);
+        // Every guess is undone except the ones on the way to the solution
+        let kept = stats.guesses - stats.backtracks;
+        assert!((1..=u64::from(stats.max_depth)).contains(&kept));
+        assert!(stats.nodes <= stats.guesses + 1);
+        assert_eq!(search_stats(&hard), stats);
+    }
+
+    #[test]
+    fn parallel_solving_keeps_file_order() {
+        let puzzles: Vec<(usize, sudoku::Sudoku)> = std::fs::read_to_string("../sudokus/sudoku17")
+            .expect("File needs to be present")
+            .lines()
+            .take(BATCH_SIZE * 5 + 7)
+            .enumerate()
+            .map(|(idx, l)| (idx + 1, sudoku::Sudoku::from_str_line(l).unwrap()))
+            .collect();
+        let solutions = |threads| {
+            let timed = if threads == 1 {
+                puzzles
+                    .iter()
+                    .map(|(_, p)| time_grid(Engine::Bitboard, *p, false).unwrap())
+                    .collect()
+            } else {
+                solve_parallel(Engine::Bitboard, &puzzles, threads, false).unwrap()
+            };
+            timed.into_iter().map(|(s, _, _)| s).collect::<Vec<_>>()
+        };
+        let sequential = solutions(1);
+        assert!(sequential.iter().all(Option::is_some));
+        assert_eq!(solutions(4), sequential);
+    }
 }
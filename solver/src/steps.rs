@@ -0,0 +1,362 @@
+use crate::render::candidates;
+use domain::Difficulty;
+use sudoku::board::positions::{CellAt, HouseType, LineType};
+use sudoku::board::Candidate;
+use sudoku::strategy::{Deduction, Strategy, StrategySolver};
+use sudoku::Sudoku;
+
+/// Techniques tried by the logical solver, easiest first, following the
+/// difficulty ratings of SudokuExplainer.
+const STRATEGIES: &[Strategy] = &[
+    Strategy::NakedSingles,
+    Strategy::HiddenSingles,
+    Strategy::LockedCandidates,
+    Strategy::NakedPairs,
+    Strategy::XWing,
+    Strategy::HiddenPairs,
+    Strategy::NakedTriples,
+    Strategy::Swordfish,
+    Strategy::HiddenTriples,
+    Strategy::NakedQuads,
+    Strategy::Jellyfish,
+    Strategy::HiddenQuads,
+];
+
+/// One deduction of a logical solve, together with the grid it was made on.
+#[derive(Debug)]
+pub struct Step {
+    pub strategy: Strategy,
+    /// The grid before this step
+    pub grid: Sudoku,
+    /// Candidates of every empty cell before this step
+    pub candidates: [[bool; 9]; 81],
+    /// Cell index and digit placed by this step, if any
+    pub placed: Option<(usize, u8)>,
+    /// Cell index and digit of every candidate this step rules out
+    pub eliminated: Vec<(usize, u8)>,
+    /// Why the step holds, in plain English
+    pub explanation: String,
+}
+
+/// The outcome of solving a puzzle the way a person would, without guessing.
+#[derive(Debug)]
+pub struct LogicalSolve {
+    pub steps: Vec<Step>,
+    /// The grid after the last step, complete only when `solved` is true
+    pub grid: Sudoku,
+    pub solved: bool,
+}
+
+/// Solves `puzzle` with human techniques only, recording every step. Puzzles
+/// that need guessing stop at the last step logic alone could make.
+pub fn logical_solve(puzzle: Sudoku) -> LogicalSolve {
+    let (solved, deductions) = match StrategySolver::from_sudoku(puzzle).solve(STRATEGIES) {
+        Ok((_, deductions)) => (true, deductions),
+        Err((_, deductions)) => (false, deductions),
+    };
+    let mut cells = puzzle.to_bytes();
+    let mut ruled_out = [[false; 9]; 81];
+    let mut steps = Vec::with_capacity(deductions.len());
+    for deduction in deductions.iter() {
+        let (placed, eliminated) = match deduction {
+            Deduction::NakedSingles(c) | Deduction::HiddenSingles(c, _) => {
+                (Some((c.cell.as_index(), c.digit.get())), vec![])
+            }
+            Deduction::LockedCandidates { conflicts, .. }
+            | Deduction::Subsets { conflicts, .. }
+            | Deduction::BasicFish { conflicts, .. } => (
+                None,
+                conflicts
+                    .iter()
+                    .map(|c| (c.cell.as_index(), c.digit.get()))
+                    .collect(),
+            ),
+            _ => continue,
+        };
+        let grid = Sudoku::from_bytes(cells).expect("Steps only fill in valid digits");
+        let mut possible = candidates(&grid);
+        for (cell, digits) in possible.iter_mut().enumerate() {
+            for (digit, possible) in digits.iter_mut().enumerate() {
+                *possible &= !ruled_out[cell][digit];
+            }
+        }
+        if let Some((cell, digit)) = placed {
+            cells[cell] = digit;
+        }
+        for &(cell, digit) in &eliminated {
+            ruled_out[cell][digit as usize - 1] = true;
+        }
+        steps.push(Step {
+            strategy: deduction.strategy(),
+            grid,
+            candidates: possible,
+            placed,
+            eliminated,
+            explanation: explain(&deduction),
+        });
+    }
+    LogicalSolve {
+        steps,
+        grid: Sudoku::from_bytes(cells).expect("Steps only fill in valid digits"),
+        solved,
+    }
+}
+
+/// Rates `puzzle` by the hardest technique the logical solver needs for it.
+/// Puzzles it cannot finish are [`Difficulty::Expert`].
+pub fn grade(puzzle: Sudoku) -> Difficulty {
+    match StrategySolver::from_sudoku(puzzle).solve(STRATEGIES) {
+        Ok((_, deductions)) => deductions
+            .iter()
+            .map(|d| match d.strategy() {
+                Strategy::NakedSingles | Strategy::HiddenSingles => Difficulty::Easy,
+                Strategy::LockedCandidates | Strategy::NakedPairs | Strategy::HiddenPairs => {
+                    Difficulty::Medium
+                }
+                _ => Difficulty::Hard,
+            })
+            .max()
+            .unwrap_or(Difficulty::Easy),
+        Err(_) => Difficulty::Expert,
+    }
+}
+
+pub fn strategy_name(strategy: &Strategy) -> &'static str {
+    match strategy {
+        Strategy::NakedSingles => "Naked single",
+        Strategy::HiddenSingles => "Hidden single",
+        Strategy::LockedCandidates => "Locked candidates",
+        Strategy::NakedPairs => "Naked pair",
+        Strategy::NakedTriples => "Naked triple",
+        Strategy::NakedQuads => "Naked quad",
+        Strategy::HiddenPairs => "Hidden pair",
+        Strategy::HiddenTriples => "Hidden triple",
+        Strategy::HiddenQuads => "Hidden quad",
+        Strategy::XWing => "X-Wing",
+        Strategy::Swordfish => "Swordfish",
+        Strategy::Jellyfish => "Jellyfish",
+        _ => "Unknown technique",
+    }
+}
+
+/// Names a cell by its 1-based row and column, e.g. `r4c7`.
+pub fn cell_name(cell: usize) -> String {
+    format!("r{}c{}", cell / 9 + 1, cell % 9 + 1)
+}
+
+fn house_name(house: HouseType) -> String {
+    match house {
+        HouseType::Row(row) => format!("row {}", row.get() + 1),
+        HouseType::Col(col) => format!("column {}", col.get() + 1),
+        HouseType::Block(block) => format!("box {}", block.get() + 1),
+    }
+}
+
+fn list<T: ToString>(items: impl IntoIterator<Item = T>) -> String {
+    let items: Vec<String> = items.into_iter().map(|i| i.to_string()).collect();
+    match items.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} and {}", rest.join(", "), last),
+        _ => items.concat(),
+    }
+}
+
+fn explain(deduction: &Deduction<&[Candidate]>) -> String {
+    match *deduction {
+        Deduction::NakedSingles(c) => format!(
+            "{} can only hold {}: every other digit already appears in its row, column or box.",
+            cell_name(c.cell.as_index()),
+            c.digit.get()
+        ),
+        Deduction::HiddenSingles(c, house) => {
+            format!(
+                "{} is the only cell left in {} that can hold {}, so {} must go there.",
+                cell_name(c.cell.as_index()),
+                house_name(house),
+                c.digit.get(),
+                c.digit.get()
+            )
+        }
+        Deduction::LockedCandidates {
+            digit,
+            miniline,
+            is_pointing,
+            ..
+        } => {
+            let cells: Vec<_> = miniline.cells().into_iter().collect();
+            let block = format!("box {}", cells[0].block().get() + 1);
+            let line = if cells.iter().all(|c| c.row() == cells[0].row()) {
+                format!("row {}", cells[0].row().get() + 1)
+            } else {
+                format!("column {}", cells[0].col().get() + 1)
+            };
+            let (within, elsewhere) = if is_pointing {
+                (block, line)
+            } else {
+                (line, block)
+            };
+            format!(
+                "Inside {}, {} can only go in the cells it shares with {}. \
+                 Whichever of them holds the {}, no other cell of {} can.",
+                within,
+                digit.get(),
+                elsewhere,
+                digit.get(),
+                elsewhere
+            )
+        }
+        Deduction::Subsets {
+            house,
+            positions,
+            digits,
+            conflicts,
+        } => {
+            let cells = list(
+                positions
+                    .into_iter()
+                    .map(|p| cell_name(house.cell_at(p).as_index())),
+            );
+            let digits = list(digits.into_iter().map(|d| d.get()));
+            let hidden = conflicts
+                .iter()
+                .any(|c| positions.into_iter().any(|p| house.cell_at(p) == c.cell));
+            if hidden {
+                format!(
+                    "In {}, the digits {} can only go in {}. Those cells must hold exactly \
+                     these digits, so every other candidate in them can be removed.",
+                    house_name(house.categorize()),
+                    digits,
+                    cells
+                )
+            } else {
+                format!(
+                    "Between them, {} can only hold {}. Those digits are used up by these \
+                     cells, so they cannot go anywhere else in {}.",
+                    cells,
+                    digits,
+                    house_name(house.categorize())
+                )
+            }
+        }
+        Deduction::BasicFish {
+            digit,
+            lines,
+            positions,
+            ..
+        } => {
+            let rows = lines
+                .into_iter()
+                .all(|l| matches!(l.categorize(), LineType::Row(_)));
+            let (line, cross) = if rows {
+                ("rows", "columns")
+            } else {
+                ("columns", "rows")
+            };
+            let lines = list(lines.into_iter().map(|l| match l.categorize() {
+                LineType::Row(r) => r.get() + 1,
+                LineType::Col(c) => c.get() + 1,
+            }));
+            let positions = list(positions.into_iter().map(|p| p.get() + 1));
+            format!(
+                "In {} {}, {} can only go in {} {}. Each of those {} gets its {} from one \
+                 of these {}, so {} cannot go anywhere else in {} {}.",
+                line,
+                lines,
+                digit.get(),
+                cross,
+                positions,
+                cross,
+                digit.get(),
+                line,
+                digit.get(),
+                cross,
+                positions
+            )
+        }
+        _ => String::new(),
+    }
+}
+
+impl Step {
+    /// A one line summary of what the step changes.
+    pub fn summary(&self) -> String {
+        match self.placed {
+            Some((cell, digit)) => format!(
+                "{}: {} = {}",
+                strategy_name(&self.strategy),
+                cell_name(cell),
+                digit
+            ),
+            None => format!(
+                "{}: removes {}",
+                strategy_name(&self.strategy),
+                self.eliminated
+                    .iter()
+                    .map(|&(cell, digit)| format!("{} from {}", digit, cell_name(cell)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{grade, logical_solve, strategy_name};
+    use domain::Difficulty;
+    use std::fs;
+    use sudoku::Sudoku;
+
+    fn grades(name: &str) -> Vec<Difficulty> {
+        fs::read_to_string(format!("../sudokus/{}.txt", name))
+            .expect("File needs to be present")
+            .lines()
+            .map(|l| grade(Sudoku::from_str_line(l).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn grades_follow_the_bundled_collections() {
+        let easy = grades("easy_sudokus");
+        let singles_only = easy.iter().filter(|&&d| d == Difficulty::Easy).count();
+        assert!(singles_only > easy.len() / 2);
+        assert!(grades("top95").iter().all(|&d| d >= Difficulty::Medium));
+    }
+
+    #[test]
+    fn explains_each_step() {
+        let puzzle = Sudoku::from_str_line(
+            "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..",
+        )
+        .unwrap();
+        let solve = logical_solve(puzzle);
+        assert!(solve.solved);
+        let first = &solve.steps[0];
+        assert_eq!(first.summary(), "Naked single: r5c6 = 4");
+        assert_eq!(
+            first.explanation,
+            "r5c6 can only hold 4: every other digit already appears in its row, column or box."
+        );
+        assert_eq!(first.grid, puzzle);
+    }
+
+    #[test]
+    fn takes_one_step_per_naked_single() {
+        let solution = Sudoku::from_str_line(
+            "483921657967345821251876493548132976729564138136798245372689514814253769695417382",
+        )
+        .unwrap();
+        let mut cells = solution.to_bytes();
+        // One cell in each of the first five rows, columns and boxes
+        for cell in [0, 12, 24, 28, 40] {
+            cells[cell] = 0;
+        }
+        let solve = logical_solve(Sudoku::from_bytes(cells).unwrap());
+        assert!(solve.solved);
+        assert_eq!(solve.grid, solution);
+        assert_eq!(solve.steps.len(), 5);
+        assert!(solve
+            .steps
+            .iter()
+            .all(|s| strategy_name(&s.strategy) == "Naked single"));
+    }
+}
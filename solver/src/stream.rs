@@ -0,0 +1,226 @@
+use crate::engine::Engine;
+use crate::error::SolverError;
+use crate::formats::{parse_puzzle, write_puzzle, Format, Puzzles};
+use crate::pretty;
+use clap::ArgEnum;
+use serde::Serialize;
+use std::io;
+use std::io::{BufRead, Write};
+use sudoku::Sudoku;
+
+/// How solutions are written when the solver is used as a filter.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One 81 character line per solution, `ERROR: <reason>` for failures
+    Line,
+    /// The solution as a 9x9 block followed by an empty line
+    Block,
+    /// One JSON object per line holding the puzzle and its solution or error
+    Json,
+    /// One 81 character line per solution using `0` for blanks
+    Sdm,
+    /// 9 rows of 9 cells, followed by an empty line
+    Sdk,
+    /// Simple Sudoku rows with `|` and `-` box borders, followed by an empty line
+    Ss,
+    /// Space separated rows, followed by an empty line
+    Grid,
+    /// Comma separated rows, followed by an empty line
+    Csv,
+    /// Rows drawn with `+`, `-` and `|` borders, followed by an empty line
+    Bordered,
+    /// Box-drawing borders with givens and solved digits highlighted, in colour on a terminal
+    Pretty,
+}
+
+impl OutputFormat {
+    fn puzzle_format(self) -> Option<Format> {
+        match self {
+            OutputFormat::Line => Some(Format::Line),
+            OutputFormat::Sdm => Some(Format::Sdm),
+            OutputFormat::Sdk => Some(Format::Sdk),
+            OutputFormat::Ss => Some(Format::Ss),
+            OutputFormat::Grid => Some(Format::Grid),
+            OutputFormat::Csv => Some(Format::Csv),
+            OutputFormat::Bordered => Some(Format::Bordered),
+            OutputFormat::Block | OutputFormat::Json | OutputFormat::Pretty => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonOutcome {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    puzzle: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    solution: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Counts of what happened to the puzzles read from the input.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StreamStats {
+    pub solved: usize,
+    pub failed: usize,
+}
+
+/// Parses a puzzle in any supported format and solves it.
+pub fn solve_one(engine: Engine, puzzle: &str) -> Result<Sudoku, SolverError> {
+    solve_sudoku(engine, parse_puzzle(puzzle)?)
+}
+
+fn solve_sudoku(engine: Engine, sudoku: Sudoku) -> Result<Sudoku, SolverError> {
+    engine
+        .solve(sudoku)?
+        .ok_or(SolverError::Unsolvable { line: None })
+}
+
+pub fn write_outcome<W: Write>(
+    out: &mut W,
+    format: OutputFormat,
+    puzzle: Option<&Sudoku>,
+    outcome: &Result<Sudoku, SolverError>,
+) -> io::Result<()> {
+    match (format, format.puzzle_format()) {
+        (_, Some(f)) if f.is_single_line() => match outcome {
+            Ok(s) => writeln!(out, "{}", write_puzzle(s, f)),
+            Err(e) => writeln!(out, "ERROR: {}", e),
+        },
+        (_, Some(f)) => match outcome {
+            Ok(s) => writeln!(out, "{}\n", write_puzzle(s, f)),
+            Err(e) => writeln!(out, "ERROR: {}\n", e),
+        },
+        (OutputFormat::Json, _) => {
+            let json = JsonOutcome {
+                puzzle: puzzle.map(|p| p.to_str_line().to_string()),
+                solution: outcome.as_ref().ok().map(|s| s.to_str_line().to_string()),
+                error: outcome.as_ref().err().map(|e| e.to_string()),
+            };
+            serde_json::to_writer(&mut *out, &json)?;
+            writeln!(out)
+        }
+        (OutputFormat::Pretty, _) => match (outcome, puzzle) {
+            (Ok(s), Some(p)) => {
+                writeln!(out, "{}\n", pretty::grid(p, Some(s), pretty::use_colour()))
+            }
+            (Ok(s), None) => writeln!(out, "{}\n", pretty::grid(s, None, pretty::use_colour())),
+            (Err(e), _) => writeln!(out, "ERROR: {}\n", e),
+        },
+        _ => match outcome {
+            Ok(s) => writeln!(out, "{}\n", s.display_block().to_string().trim_start()),
+            Err(e) => writeln!(out, "ERROR: {}\n", e),
+        },
+    }
+}
+
+/// Reads puzzles from `input`, in `input_format` or whatever format the
+/// first puzzle looks like, and writes exactly one result per puzzle to
+/// `output`, so the solver can sit in the middle of a shell pipeline. A
+/// closed output pipe ends the stream quietly.
+pub fn solve_stream<R: BufRead, W: Write>(
+    input: R,
+    output: &mut W,
+    engine: Engine,
+    input_format: Option<Format>,
+    format: OutputFormat,
+) -> io::Result<StreamStats> {
+    let mut stats = StreamStats::default();
+    for entry in Puzzles::new(input, input_format) {
+        let entry = entry?;
+        let puzzle = entry.puzzle.as_ref().ok().copied();
+        let outcome = match entry.puzzle {
+            Ok(p) => solve_sudoku(engine, p),
+            Err(e) => Err(e),
+        }
+        .map_err(|e| e.at_line(entry.line));
+        match outcome {
+            Ok(_) => stats.solved += 1,
+            // The engine could not run, and will not for the next puzzle
+            Err(SolverError::Io(e)) => return Err(e),
+            Err(_) => stats.failed += 1,
+        }
+        match write_outcome(output, format, puzzle.as_ref(), &outcome) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(stats),
+            r => r?,
+        }
+    }
+    match output.flush() {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(stats),
+        r => r.map(|_| stats),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{solve_stream, OutputFormat, StreamStats};
+    use crate::engine::Engine;
+    use std::io;
+    use std::io::{Cursor, Write};
+    use sudoku::Sudoku;
+
+    const PUZZLES: [&str; 2] = [
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..",
+        "4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......",
+    ];
+
+    fn input() -> Cursor<String> {
+        Cursor::new(format!("{}\n123\n{}\n", PUZZLES[0], PUZZLES[1]))
+    }
+
+    fn solution(puzzle: &str) -> String {
+        let sudoku = Sudoku::from_str_line(puzzle).unwrap();
+        sudoku.solve_unique().unwrap().to_str_line().to_string()
+    }
+
+    #[test]
+    fn writes_one_line_per_puzzle() {
+        let mut out = vec![];
+        let stats = solve_stream(input(), &mut out, Engine::Native, None, OutputFormat::Line);
+        assert_eq!(
+            stats.unwrap(),
+            StreamStats {
+                solved: 2,
+                failed: 1
+            }
+        );
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], solution(PUZZLES[0]));
+        assert!(lines[1].starts_with("ERROR: line 2: "), "{}", lines[1]);
+        assert_eq!(lines[2], solution(PUZZLES[1]));
+    }
+
+    /// Takes `left` bytes, then fails as if the reader went away.
+    struct ClosingPipe {
+        left: usize,
+    }
+
+    impl Write for ClosingPipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.left < buf.len() {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            self.left -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn stops_quietly_when_the_output_closes() {
+        let mut out = ClosingPipe { left: 82 };
+        let stats = solve_stream(input(), &mut out, Engine::Native, None, OutputFormat::Line);
+        assert_eq!(
+            stats.unwrap(),
+            StreamStats {
+                solved: 1,
+                failed: 1
+            }
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use crate::steps::cell_name;
+use std::fmt::{Display, Formatter};
+use sudoku::Sudoku;
+
+/// Something wrong with a puzzle and the solution paired with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The solution leaves this many cells empty
+    Incomplete(usize),
+    /// The solution repeats a digit in some row, column or box
+    Conflicting,
+    /// Givens the solution changes, as (cell, given, digit in the solution)
+    Givens(Vec<(usize, u8, u8)>),
+    /// The puzzle has no solution at all
+    Unsolvable,
+    /// The puzzle has more than one solution
+    NotUnique,
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mismatch::Incomplete(empty) => write!(f, "solution leaves {} cells empty", empty),
+            Mismatch::Conflicting => {
+                write!(f, "solution repeats a digit in a row, column or box")
+            }
+            Mismatch::Givens(cells) => {
+                let cells: Vec<String> = cells
+                    .iter()
+                    .map(|&(cell, given, found)| {
+                        format!("{} is {} not {}", cell_name(cell), found, given)
+                    })
+                    .collect();
+                write!(f, "solution changes givens: {}", cells.join(", "))
+            }
+            Mismatch::Unsolvable => write!(f, "puzzle has no solution"),
+            Mismatch::NotUnique => write!(f, "puzzle has more than one solution"),
+        }
+    }
+}
+
+/// Checks that `solution` is a valid grid, keeps every given of `puzzle`, and
+/// is the only solution the puzzle has.
+pub fn check(puzzle: &Sudoku, solution: &Sudoku) -> Vec<Mismatch> {
+    let mut mismatches = vec![];
+    let (givens, cells) = (puzzle.to_bytes(), solution.to_bytes());
+    let empty = cells.iter().filter(|&&d| d == 0).count();
+    if empty > 0 {
+        mismatches.push(Mismatch::Incomplete(empty));
+    } else if !solution.is_solved() {
+        mismatches.push(Mismatch::Conflicting);
+    }
+    let changed: Vec<(usize, u8, u8)> = givens
+        .iter()
+        .zip(cells.iter())
+        .enumerate()
+        .filter(|(_, (&given, &found))| given != 0 && found != 0 && given != found)
+        .map(|(cell, (&given, &found))| (cell, given, found))
+        .collect();
+    if !changed.is_empty() {
+        mismatches.push(Mismatch::Givens(changed));
+    }
+    match puzzle.count_at_most(2) {
+        0 => mismatches.push(Mismatch::Unsolvable),
+        1 => {}
+        _ => mismatches.push(Mismatch::NotUnique),
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check, Mismatch};
+    use std::fs;
+    use sudoku::Sudoku;
+
+    fn pairs(name: &str) -> Vec<(Sudoku, Sudoku)> {
+        let read = |file: String| fs::read_to_string(file).expect("File needs to be present");
+        let puzzles = read(format!("../sudokus/{}_sudokus.txt", name));
+        let solutions = read(format!("../sudokus/solved_{}_sudokus.txt", name));
+        puzzles
+            .lines()
+            .zip(solutions.lines())
+            .map(|(p, s)| {
+                (
+                    Sudoku::from_str_line(p).unwrap(),
+                    Sudoku::from_str_line(s).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bundled_solutions_match_their_puzzles() {
+        for name in ["easy", "medium", "hard"] {
+            for (puzzle, solution) in pairs(name) {
+                assert_eq!(check(&puzzle, &solution), vec![], "{}", puzzle);
+            }
+        }
+    }
+
+    #[test]
+    fn reports_changed_givens() {
+        let (puzzle, solution) = pairs("easy")[0];
+        let given = puzzle.to_bytes().iter().position(|&d| d != 0).unwrap();
+        let mut cells = solution.to_bytes();
+        let digit = cells[given] % 9 + 1;
+        cells[given] = digit;
+        let wrong = Sudoku::from_bytes(cells).unwrap();
+        let mismatches = check(&puzzle, &wrong);
+        assert!(mismatches.contains(&Mismatch::Conflicting));
+        let changed = Mismatch::Givens(vec![(given, puzzle.to_bytes()[given], digit)]);
+        assert!(mismatches.contains(&changed), "{:?}", mismatches);
+    }
+}